@@ -8,7 +8,7 @@ module Native =
     extern int rrpc_init()
 
     [<DllImport("rrpc_core", CallingConvention = CallingConvention.Cdecl, CharSet = CharSet.Ansi)>]
-    extern int rrpc_call(IntPtr method_ptr, UIntPtr method_len, IntPtr in_ptr, UIntPtr in_len, out IntPtr out_ptr, out UIntPtr out_len)
+    extern int rrpc_call(IntPtr method_ptr, IntPtr in_ptr, UIntPtr in_len, out IntPtr out_ptr, out UIntPtr out_len)
 
     [<DllImport("rrpc_core", CallingConvention = CallingConvention.Cdecl, CharSet = CharSet.Ansi)>]
     extern void rrpc_free(IntPtr ptr, UIntPtr len)
@@ -20,7 +20,8 @@ module RRpc =
 
     let call (methodName:string) (input:byte[]) : Result<byte[], string> =
         // Pin/allocate input
-        let methodBytes = System.Text.Encoding.UTF8.GetBytes(methodName)
+        // Null-terminated, as rrpc_call expects
+        let methodBytes = System.Text.Encoding.UTF8.GetBytes(methodName + "\u0000")
         let methodPtr = Marshal.AllocHGlobal(methodBytes.Length)
         Marshal.Copy(methodBytes, 0, methodPtr, methodBytes.Length)
 
@@ -32,7 +33,7 @@ module RRpc =
         let mutable outLen = UIntPtr.Zero
 
         try
-            let rc = Native.rrpc_call(methodPtr, UIntPtr(uint32 methodBytes.Length), inPtr, UIntPtr(uint32 inputLen), &outPtr, &outLen)
+            let rc = Native.rrpc_call(methodPtr, inPtr, UIntPtr(uint32 inputLen), &outPtr, &outLen)
             if rc <> 0 then
                 Error (sprintf "rrpc_call returned %.d" rc)
             else
@@ -23,3 +23,210 @@ module Native =
 
     [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_free")>]
     extern void rrpc_free(IntPtr ptr, UIntPtr len)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_last_error")>]
+    extern int rrpc_last_error(out IntPtr out_ptr, out UIntPtr out_len)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_clear_error")>]
+    extern void rrpc_clear_error()
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_runtime_new")>]
+    extern IntPtr rrpc_runtime_new()
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_runtime_free")>]
+    extern void rrpc_runtime_free(IntPtr rt)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_call_rt")>]
+    extern int rrpc_call_rt(IntPtr rt, IntPtr method_ptr, IntPtr in_ptr, UIntPtr in_len, out IntPtr out_ptr, out UIntPtr out_len)
+
+    /// Completion callback for rrpc_call_async; the receiver must rrpc_free the output buffer
+    [<UnmanagedFunctionPointer(CallingConvention.Cdecl)>]
+    type CompletionCallback = delegate of callId: uint64 * rc: int * outPtr: IntPtr * outLen: UIntPtr * userData: IntPtr -> unit
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_call_async")>]
+    extern uint64 rrpc_call_async(IntPtr method_ptr, IntPtr in_ptr, UIntPtr in_len, CompletionCallback callback, IntPtr user_data)
+
+    /// Per-frame callback for rrpc_call_stream; return 0 to continue, non-zero to stop
+    [<UnmanagedFunctionPointer(CallingConvention.Cdecl)>]
+    type StreamItemCallback = delegate of itemPtr: IntPtr * itemLen: UIntPtr * userData: IntPtr -> int
+
+    /// End-of-stream callback for rrpc_call_stream (rc = 0 on normal completion)
+    [<UnmanagedFunctionPointer(CallingConvention.Cdecl)>]
+    type StreamDoneCallback = delegate of rc: int * userData: IntPtr -> unit
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_call_stream")>]
+    extern int rrpc_call_stream(IntPtr method_ptr, IntPtr in_ptr, UIntPtr in_len, StreamItemCallback on_item, StreamDoneCallback on_done, IntPtr user_data)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_cancel_token_new")>]
+    extern IntPtr rrpc_cancel_token_new()
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_cancel")>]
+    extern void rrpc_cancel(IntPtr token)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_cancel_token_free")>]
+    extern void rrpc_cancel_token_free(IntPtr token)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_call_with_token")>]
+    extern int rrpc_call_with_token(IntPtr method_ptr, IntPtr in_ptr, UIntPtr in_len, IntPtr token, out IntPtr out_ptr, out UIntPtr out_len)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_call_with_timeout")>]
+    extern int rrpc_call_with_timeout(IntPtr method_ptr, IntPtr in_ptr, UIntPtr in_len, uint64 timeout_ms, IntPtr token, out IntPtr out_ptr, out UIntPtr out_len)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_call_into")>]
+    extern int rrpc_call_into(IntPtr method_ptr, IntPtr in_ptr, UIntPtr in_len, IntPtr buf_ptr, UIntPtr buf_cap, out UIntPtr out_len)
+
+    /// One call in a rrpc_call_batch request
+    [<Struct; StructLayout(LayoutKind.Sequential)>]
+    type BatchEntry =
+        val mutable Method: IntPtr
+        val mutable InPtr: IntPtr
+        val mutable InLen: UIntPtr
+
+    /// Outcome of one rrpc_call_batch entry; OutPtr must be released with rrpc_free
+    [<Struct; StructLayout(LayoutKind.Sequential)>]
+    type BatchResult =
+        val mutable Rc: int
+        val mutable OutPtr: IntPtr
+        val mutable OutLen: UIntPtr
+
+    [<Literal>]
+    let BatchParallel = 1u
+
+    [<Literal>]
+    let BatchStopOnError = 2u
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_call_batch")>]
+    extern int rrpc_call_batch(BatchEntry[] entries, UIntPtr count, uint32 flags, [<Out>] BatchResult[] results)
+
+    /// rrpc_call_batch with every entry limited to `token`'s capabilities (ABI 1.8)
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_call_batch_with_capabilities")>]
+    extern int rrpc_call_batch_with_capabilities(BatchEntry[] entries, UIntPtr count, uint32 flags, IntPtr token, [<Out>] BatchResult[] results)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_list_methods")>]
+    extern int rrpc_list_methods(out IntPtr out_ptr, out UIntPtr out_len)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_has_method")>]
+    extern int rrpc_has_method(IntPtr method_ptr)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_shutdown")>]
+    extern int rrpc_shutdown(out UIntPtr outstanding)
+
+    /// Host method exposed to Rust via rrpc_register_host; returns 0 or an error code
+    [<UnmanagedFunctionPointer(CallingConvention.Cdecl)>]
+    type HostMethod = delegate of inPtr: IntPtr * inLen: UIntPtr * outPtr: byref<IntPtr> * outLen: byref<UIntPtr> * userData: IntPtr -> int
+
+    /// Releases an output buffer returned by a HostMethod
+    [<UnmanagedFunctionPointer(CallingConvention.Cdecl)>]
+    type HostFreeFn = delegate of ptr: IntPtr * len: UIntPtr * userData: IntPtr -> unit
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_register_host")>]
+    extern int rrpc_register_host(IntPtr method_ptr, HostMethod func, IntPtr user_data, HostFreeFn free_fn)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_load_plugin")>]
+    extern int rrpc_load_plugin([<MarshalAs(UnmanagedType.LPUTF8Str)>] string path, [<MarshalAs(UnmanagedType.LPUTF8Str)>] string ns)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_unload_plugin")>]
+    extern int rrpc_unload_plugin([<MarshalAs(UnmanagedType.LPUTF8Str)>] string ns)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_stats")>]
+    extern int rrpc_stats(out IntPtr out_ptr, out UIntPtr out_len)
+
+    /// Receives log records forwarded by rrpc_set_log_callback (level 1 = error .. 5 = trace)
+    [<UnmanagedFunctionPointer(CallingConvention.Cdecl)>]
+    type LogCallback = delegate of level: int * targetPtr: IntPtr * targetLen: UIntPtr * msgPtr: IntPtr * msgLen: UIntPtr * userData: IntPtr -> unit
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_set_log_callback")>]
+    extern int rrpc_set_log_callback(LogCallback callback, IntPtr user_data)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_set_log_level")>]
+    extern int rrpc_set_log_level(int level)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_capability_token_new")>]
+    extern IntPtr rrpc_capability_token_new()
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_grant")>]
+    extern int rrpc_grant(IntPtr token, [<MarshalAs(UnmanagedType.LPUTF8Str)>] string capability)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_capability_token_free")>]
+    extern void rrpc_capability_token_free(IntPtr token)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_call_with_capabilities")>]
+    extern int rrpc_call_with_capabilities(IntPtr method_ptr, IntPtr in_ptr, UIntPtr in_len, IntPtr token, out IntPtr out_ptr, out UIntPtr out_len)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_require_capability")>]
+    extern int rrpc_require_capability([<MarshalAs(UnmanagedType.LPUTF8Str)>] string pattern, [<MarshalAs(UnmanagedType.LPUTF8Str)>] string capability)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_grant_default")>]
+    extern int rrpc_grant_default([<MarshalAs(UnmanagedType.LPUTF8Str)>] string capability)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_capability_token_new_named")>]
+    extern IntPtr rrpc_capability_token_new_named([<MarshalAs(UnmanagedType.LPUTF8Str)>] string identity)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_audit_file")>]
+    extern int rrpc_audit_file([<MarshalAs(UnmanagedType.LPUTF8Str)>] string path, byte[] key, UIntPtr key_len)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_audit_methods")>]
+    extern int rrpc_audit_methods([<MarshalAs(UnmanagedType.LPUTF8Str)>] string pattern)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_audit_verify")>]
+    extern int rrpc_audit_verify([<MarshalAs(UnmanagedType.LPUTF8Str)>] string path, byte[] key, UIntPtr key_len, out uint64 out_records, out uint64 out_broken_line)
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_abi_version")>]
+    extern uint32 rrpc_abi_version()
+
+    [<DllImport(LibName, CallingConvention = CallingConvention.Cdecl, EntryPoint = "rrpc_features")>]
+    extern uint64 rrpc_features()
+
+    /// ABI major version these bindings were written against (see docs/api-reference.md, "ABI Versioning")
+    [<Literal>]
+    let AbiMajor = 1us
+
+    /// Feature bits (FEATURE_* in the native library); each binding checks the one it needs on use
+    [<Literal>]
+    let FeatureLastError = 0x1UL
+
+    [<Literal>]
+    let FeatureLifecycle = 0x200UL
+
+    [<Literal>]
+    let FeatureIntrospection = 0x100UL
+
+    [<Literal>]
+    let FeatureHostMethods = 0x400UL
+
+    [<Literal>]
+    let FeatureStats = 0x1000UL
+
+    [<Literal>]
+    let FeatureLogCallback = 0x2000UL
+
+    [<Literal>]
+    let FeatureCapabilities = 0x4000UL
+
+    [<Literal>]
+    let FeatureAudit = 0x8000UL
+
+    /// Refuse a native library with another major ABI version
+    ///
+    /// Newer minor versions only add exports and feature bits; older ones may lack some, which
+    /// requireFeature reports when a binding that needs them is called.
+    let checkAbi () : Result<unit, string> =
+        let version = rrpc_abi_version ()
+        let major = uint16 (version >>> 16)
+        let minor = uint16 (version &&& 0xffffu)
+        if major <> AbiMajor then
+            Error (sprintf "%s implements rRPC ABI %d.%d; these bindings need %d.x" LibName major minor AbiMajor)
+        else
+            Ok ()
+
+    // Read on first use, so libraries without any optional feature work until one is needed
+    let private features = lazy (rrpc_features ())
+
+    /// Whether the native library implements `feature`
+    let hasFeature (feature: uint64) : bool =
+        features.Force() &&& feature = feature
+
+    /// Fail with a clear message if the native library lacks `feature`, needed by `api`
+    let requireFeature (feature: uint64) (api: string) : Result<unit, string> =
+        if hasFeature feature then Ok ()
+        else Error (sprintf "%s does not implement %s (rRPC feature 0x%x); upgrade the native library" LibName api feature)
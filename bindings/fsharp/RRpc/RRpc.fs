@@ -16,48 +16,258 @@ module RRpcClient =
         | :? EntryPointNotFoundException as e -> Error (sprintf "native function not found: %s" e.Message)
         | ex -> Error (sprintf "native call error: %s" ex.Message)
 
+    /// Fail unless the native library implements `feature`, which `api` needs
+    let private requires (feature: uint64) (api: string) : Result<unit, string> =
+        safeCallNative(fun () -> Native.requireFeature feature api)
+        |> Result.bind id
+
+    /// safeCallNative for a native call returning a Result, checking `feature` first
+    let private callFeature (feature: uint64) (api: string) (f: unit -> Result<'T, string>) : Result<'T, string> =
+        requires feature api
+        |> Result.bind (fun () -> safeCallNative f |> Result.bind id)
+
     /// Initialize runtime (returns Ok 0 on success or Error string)
+    ///
+    /// Fails without initializing if the native library's ABI is incompatible.
     let init () : Result<int, string> =
-        safeCallNative(fun () -> Native.rrpc_init())
+        safeCallNative(fun () -> Native.checkAbi ())
+        |> Result.bind id
+        |> Result.bind (fun () -> safeCallNative(fun () -> Native.rrpc_init()))
 
-    /// Call a method (method name is UTF8 string, input byte[]). Returns Ok bytes or Error string.
-    let call (methodName: string) (input: byte[]) : Result<byte[], string> =
+    /// Shut the native runtime down, draining in-flight calls; returns the number of unfreed buffers
+    let shutdown () : Result<uint64, string> =
+        callFeature Native.FeatureLifecycle "rrpc_shutdown" (fun () ->
+            let mutable outstanding = UIntPtr.Zero
+            let rc = Native.rrpc_shutdown(&outstanding)
+            if rc <> 0 then Error (sprintf "rrpc_shutdown failed: rc=%d" rc)
+            else Ok (uint64 outstanding))
+
+    /// Read (and clear) the last error message recorded on this thread by the native runtime
+    let lastError () : string option =
+        let mutable ptr = IntPtr.Zero
+        let mutable len = UIntPtr.Zero
+        if not (Native.hasFeature Native.FeatureLastError) then None
+        elif Native.rrpc_last_error(&ptr, &len) <> 0 || ptr = IntPtr.Zero then None
+        else
+            let n = int (uint64 len)
+            let msg = Marshal.PtrToStringUTF8(ptr, n)
+            Native.rrpc_free(ptr, len)
+            Native.rrpc_clear_error()
+            Some msg
+
+    /// Call through rrpc_call, or through rrpc_call_with_capabilities when given a token
+    let private callNative (token: IntPtr option) (methodName: string) (input: byte[]) : Result<byte[], string> =
         // Encode method name to UTF8 and ensure null-terminated for C
         let methodBytes = Encoding.UTF8.GetBytes(methodName + "\u0000")
+        let input = if isNull input then [||] else input
+        let name = if token.IsSome then "rrpc_call_with_capabilities" else "rrpc_call"
         // Pin arrays
+        let mb = GCHandle.Alloc(methodBytes, GCHandleType.Pinned)
+        let ib = GCHandle.Alloc(input, GCHandleType.Pinned)
         try
-            use mb = GCHandle.Alloc(methodBytes, GCHandleType.Pinned)
-            use ib = GCHandle.Alloc(input, GCHandleType.Pinned)
             let mptr = mb.AddrOfPinnedObject()
             let iptr = if input.Length = 0 then IntPtr.Zero else ib.AddrOfPinnedObject()
 
-            // Call native
-            match safeCallNative(fun () ->
-                    let mutable outPtr = IntPtr.Zero
-                    let mutable outLen = UintPtr.Zero
-                    let rc = Native.rrpc_call(mptr, iptr, (uint input.Length |> UIntPtr), &outPtr, &outLen)
-                    if rc <> 0 then rc else 0
-            ) with
-            | Error e -> Error e
-            | Ok rc ->
-                if rc <> 0 then Error (sprintf "rrpc_call failed: rc=%d" rc)
+            safeCallNative(fun () ->
+                let mutable outPtr = IntPtr.Zero
+                let mutable outLen = UIntPtr.Zero
+                let rc =
+                    match token with
+                    | Some token -> Native.rrpc_call_with_capabilities(mptr, iptr, UIntPtr(uint input.Length), token, &outPtr, &outLen)
+                    | None -> Native.rrpc_call(mptr, iptr, UIntPtr(uint input.Length), &outPtr, &outLen)
+                if rc <> 0 then
+                    match lastError () with
+                    | Some msg -> Error (sprintf "%s failed: rc=%d: %s" name rc msg)
+                    | None -> Error (sprintf "%s failed: rc=%d" name rc)
+                elif outPtr = IntPtr.Zero || outLen = UIntPtr.Zero then Ok [||]
                 else
-                    // If succeeded, we already copied the result above; but due to safeCallNative closure limitations,
-                    // re-implement call flow and return actual bytes safely below.
-                    // We'll implement a direct version now.
-                    if rc <> 0 then Error (sprintf "rrpc_call failed: rc=%d" rc)
-                    else
-                        if outPtr = IntPtr.Zero || outLen = UIntPtr.Zero then Ok [||]
-                        else
-                            // Convert UIntPtr to int safely
-                            let len = int (uint64 outLen)
-                            if len <= 0 then Ok [||] else
-                            let out = Array.zeroCreate len
-                            Marshal.Copy(outPtr, out, 0, len)
-                            Native.rrpc_free(outPtr, outLen)
-                            Ok out
+                    let len = int (uint64 outLen)
+                    let out = Array.zeroCreate len
+                    Marshal.Copy(outPtr, out, 0, len)
+                    Native.rrpc_free(outPtr, outLen)
+                    Ok out)
+            |> Result.bind id
         finally
-            ()
+            mb.Free()
+            ib.Free()
+
+    /// Call a method (method name is UTF8 string, input byte[]). Returns Ok bytes or Error string.
+    let call (methodName: string) (input: byte[]) : Result<byte[], string> =
+        callNative None methodName input
+
+    let private grantAll (token: IntPtr) (grants: string list) : Result<IntPtr, string> =
+        match grants |> List.tryFind (fun grant -> Native.rrpc_grant(token, grant) <> 0) with
+        | Some grant ->
+            Native.rrpc_capability_token_free(token)
+            Error (sprintf "rrpc_grant failed for '%s'" grant)
+        | None -> Ok token
+
+    /// Create a capability token granting `grants` (names or `*` patterns); release it with freeCapabilities
+    let createCapabilities (grants: string list) : Result<IntPtr, string> =
+        callFeature Native.FeatureCapabilities "rrpc_capability_token_new" (fun () ->
+            grantAll (Native.rrpc_capability_token_new()) grants)
+
+    /// Like createCapabilities, attributing calls made with the token to `identity` in the audit log
+    let createNamedCapabilities (identity: string) (grants: string list) : Result<IntPtr, string> =
+        callFeature Native.FeatureAudit "rrpc_capability_token_new_named" (fun () ->
+            match Native.rrpc_capability_token_new_named(identity) with
+            | token when token = IntPtr.Zero -> Error "rrpc_capability_token_new_named failed"
+            | token -> grantAll token grants)
+
+    /// Release a token from createCapabilities or createNamedCapabilities
+    let freeCapabilities (token: IntPtr) : unit =
+        Native.rrpc_capability_token_free(token)
+
+    /// Call a method with only the capabilities granted to `token` (e.g. on behalf of a script)
+    let callWithCapabilities (token: IntPtr) (methodName: string) (input: byte[]) : Result<byte[], string> =
+        requires Native.FeatureCapabilities "rrpc_call_with_capabilities"
+        |> Result.bind (fun () -> callNative (Some token) methodName input)
+
+    /// Require `capability` for every method matching `pattern` ("*", "prefix.*" or an exact name)
+    let requireCapability (pattern: string) (capability: string) : Result<unit, string> =
+        callFeature Native.FeatureCapabilities "rrpc_require_capability" (fun () ->
+            match Native.rrpc_require_capability(pattern, capability) with
+            | 0 -> Ok ()
+            | rc -> Error (sprintf "rrpc_require_capability failed: rc=%d" rc))
+
+    /// Grant `capability` to calls made without a token, such as `call`
+    let grantDefault (capability: string) : Result<unit, string> =
+        callFeature Native.FeatureCapabilities "rrpc_grant_default" (fun () ->
+            match Native.rrpc_grant_default(capability) with
+            | 0 -> Ok ()
+            | rc -> Error (sprintf "rrpc_grant_default failed: rc=%d" rc))
+
+    /// Append calls of audited methods to a log file chained with HMAC-SHA256 under `key`
+    /// (continuing an existing log intact under the same key). The key is not written to the log,
+    /// so without it records cannot be edited undetectably; dropping the newest records can only be
+    /// caught by comparing with a record count kept elsewhere. Load the key from a secret store.
+    let auditToFile (path: string) (key: byte[]) : Result<unit, string> =
+        callFeature Native.FeatureAudit "rrpc_audit_file" (fun () ->
+            match Native.rrpc_audit_file(path, key, UIntPtr(uint key.Length)) with
+            | 0 -> Ok ()
+            | rc ->
+                match lastError () with
+                | Some msg -> Error (sprintf "rrpc_audit_file failed: rc=%d: %s" rc msg)
+                | None -> Error (sprintf "rrpc_audit_file failed: rc=%d" rc))
+
+    /// Audit every method matching `pattern` ("*", "prefix.*" or an exact name)
+    let auditMethods (pattern: string) : Result<unit, string> =
+        callFeature Native.FeatureAudit "rrpc_audit_methods" (fun () ->
+            match Native.rrpc_audit_methods(pattern) with
+            | 0 -> Ok ()
+            | rc -> Error (sprintf "rrpc_audit_methods failed: rc=%d" rc))
+
+    /// Verify an audit log with the key it was written with: Ok records if every record's MAC
+    /// checks out, Error naming the first tampered line (line 1 for a wrong key) otherwise.
+    /// Truncated logs verify; compare the count with one kept elsewhere to detect that.
+    let verifyAudit (path: string) (key: byte[]) : Result<uint64, string> =
+        callFeature Native.FeatureAudit "rrpc_audit_verify" (fun () ->
+            let mutable records = 0UL
+            let mutable broken = 0UL
+            match Native.rrpc_audit_verify(path, key, UIntPtr(uint key.Length), &records, &broken) with
+            | 0 when broken = 0UL -> Ok records
+            | 0 -> Error (sprintf "audit log %s is broken at line %d (%d records intact)" path broken records)
+            | rc -> Error (sprintf "rrpc_audit_verify failed: rc=%d" rc))
+
+    /// Names of the methods registered in the native runtime
+    let listMethods () : Result<string list, string> =
+        callFeature Native.FeatureIntrospection "rrpc_list_methods" (fun () ->
+            let mutable outPtr = IntPtr.Zero
+            let mutable outLen = UIntPtr.Zero
+            let rc = Native.rrpc_list_methods(&outPtr, &outLen)
+            if rc <> 0 then
+                Error (sprintf "rrpc_list_methods failed: rc=%d" rc)
+            else
+                let json = Marshal.PtrToStringUTF8(outPtr, int (uint64 outLen))
+                Native.rrpc_free(outPtr, outLen)
+                use doc = JsonDocument.Parse(json)
+                doc.RootElement.EnumerateArray()
+                |> Seq.map (fun m -> m.GetProperty("name").GetString())
+                |> List.ofSeq
+                |> Ok)
+
+    /// Per-method call statistics as the JSON document returned by rrpc_stats
+    let stats () : Result<string, string> =
+        callFeature Native.FeatureStats "rrpc_stats" (fun () ->
+            let mutable outPtr = IntPtr.Zero
+            let mutable outLen = UIntPtr.Zero
+            let rc = Native.rrpc_stats(&outPtr, &outLen)
+            if rc <> 0 then
+                Error (sprintf "rrpc_stats failed: rc=%d" rc)
+            else
+                let json = Marshal.PtrToStringUTF8(outPtr, int (uint64 outLen))
+                Native.rrpc_free(outPtr, outLen)
+                Ok json)
+
+    /// Check that every expected method is registered (e.g. at startup)
+    let requireMethods (expected: string list) : Result<unit, string> =
+        listMethods ()
+        |> Result.bind (fun present ->
+            match expected |> List.filter (fun m -> not (List.contains m present)) with
+            | [] -> Ok ()
+            | missing -> Error (sprintf "missing native methods: %s" (String.Join(", ", missing))))
+
+    // Delegates passed to rrpc_register_host must not be collected while registered
+    let private hostDelegates = System.Collections.Concurrent.ConcurrentDictionary<string, obj>()
+
+    let private hostFree = Native.HostFreeFn(fun ptr _ _ -> Marshal.FreeHGlobal(ptr))
+
+    let private toNative (bytes: byte[]) (outPtr: byref<IntPtr>) (outLen: byref<UIntPtr>) =
+        if bytes.Length > 0 then
+            outPtr <- Marshal.AllocHGlobal(bytes.Length)
+            Marshal.Copy(bytes, 0, outPtr, bytes.Length)
+        outLen <- UIntPtr(uint bytes.Length)
+
+    /// Expose an F# function as an rRPC method that Rust handlers can call (may run on any thread)
+    let registerHost (methodName: string) (handler: byte[] -> Result<byte[], string>) : Result<unit, string> =
+        let func =
+            Native.HostMethod(fun inPtr inLen outPtr outLen _ ->
+                let input = Array.zeroCreate<byte> (int (uint64 inLen))
+                if input.Length > 0 then Marshal.Copy(inPtr, input, 0, input.Length)
+                match (try handler input with ex -> Error ex.Message) with
+                | Ok output ->
+                    toNative output &outPtr &outLen
+                    0
+                | Error msg ->
+                    toNative (Encoding.UTF8.GetBytes msg) &outPtr &outLen
+                    99)
+        let methodBytes = Encoding.UTF8.GetBytes(methodName + "\u0000")
+        let mb = GCHandle.Alloc(methodBytes, GCHandleType.Pinned)
+        try
+            callFeature Native.FeatureHostMethods "rrpc_register_host" (fun () ->
+                let rc = Native.rrpc_register_host(mb.AddrOfPinnedObject(), func, IntPtr.Zero, hostFree)
+                if rc <> 0 then
+                    Error (sprintf "rrpc_register_host failed: rc=%d" rc)
+                else
+                    hostDelegates.[methodName] <- box func
+                    Ok ())
+        finally
+            mb.Free()
+
+    // The delegate passed to rrpc_set_log_callback must not be collected while installed
+    let mutable private logDelegate : Native.LogCallback = null
+
+    /// Forward Rust log records to `sink level target message` (level 1 = error .. 5 = trace; may run on any thread)
+    let setLogSink (sink: int -> string -> string -> unit) : Result<unit, string> =
+        let callback =
+            Native.LogCallback(fun level targetPtr targetLen msgPtr msgLen _ ->
+                let target = Marshal.PtrToStringUTF8(targetPtr, int (uint64 targetLen))
+                let message = Marshal.PtrToStringUTF8(msgPtr, int (uint64 msgLen))
+                try sink level target message with _ -> ())
+        callFeature Native.FeatureLogCallback "rrpc_set_log_callback" (fun () ->
+            let rc = Native.rrpc_set_log_callback(callback, IntPtr.Zero)
+            if rc <> 0 then
+                Error (sprintf "rrpc_set_log_callback failed: rc=%d" rc)
+            else
+                logDelegate <- callback
+                Ok ())
+
+    /// Set the most verbose level forwarded to the log sink (0 = off .. 5 = trace)
+    let setLogLevel (level: int) : Result<unit, string> =
+        callFeature Native.FeatureLogCallback "rrpc_set_log_level" (fun () ->
+            match Native.rrpc_set_log_level(level) with
+            | 0 -> Ok ()
+            | rc -> Error (sprintf "rrpc_set_log_level failed: rc=%d" rc))
 
     /// Helper: call and decode JSON result as a given type
     let callJson<'T> (methodName: string) (input: obj) : Result<'T, string> =
@@ -0,0 +1,467 @@
+//! Tamper-evident audit log of privileged calls
+//!
+//! Methods flagged with `MethodConfig::audited`, or matched by a
+//! `Registry::audit` pattern, produce one `AuditRecord` per call attempt,
+//! including attempts rejected before the handler ran (for example with
+//! `RpcError::PermissionDenied`). Records go to the registry's
+//! `AuditSink`, set with `Registry::set_audit_sink`; without a sink nothing
+//! is recorded. A sink that fails is reported with `log::error!` and does
+//! not change the outcome of the call.
+//!
+//! `FileSink` appends records to a file as JSON lines, each carrying an
+//! HMAC-SHA256 over itself and the MAC of the previous record, keyed with a
+//! secret supplied by the host. The key is never written to the log, so
+//! `verify` with the same key detects records edited, removed, reordered or
+//! inserted by anyone who can write the file but does not know the key.
+//!
+//! What it does not detect:
+//! - Truncation of the newest records, or removal of the whole file. Keep
+//!   `Verification::last_mac` (or the record count) outside the log and
+//!   compare it to catch that.
+//! - Rewrites by someone holding the key. The key lives in the memory of the
+//!   process writing the log, so handlers running in that process can read
+//!   it; keep it out of files they can reach and out of the log's directory.
+//!
+//! # Example
+//! ```no_run
+//! use rrpc_core::audit::{self, FileSink};
+//! use rrpc_core::Registry;
+//! use std::sync::Arc;
+//!
+//! let key = std::env::var("AUDIT_KEY").unwrap_or_default();
+//! let mut registry = Registry::new();
+//! registry.register("udg.delete_graph", |_| Ok(vec![])).audited();
+//! registry.set_audit_sink(Some(Arc::new(FileSink::open("audit.log", key.as_bytes())?)));
+//!
+//! registry.call("udg.delete_graph", b"{\"id\":7}")?;
+//! assert!(audit::verify("audit.log", key.as_bytes())?.is_intact());
+//! # Ok::<(), rrpc_core::RpcError>(())
+//! ```
+
+use crate::error::RpcError;
+use crate::introspect::push_str;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::Write as _;
+use std::os::raw::c_int;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// `prev` of the first record in a file
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Length of a hex-encoded HMAC-SHA256
+const MAC_LEN: usize = 64;
+
+/// Block size of SHA-256, which HMAC pads the key to
+const BLOCK_LEN: usize = 64;
+
+/// One audited call attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// When the call finished
+    pub timestamp: SystemTime,
+    /// Method name
+    pub method: String,
+    /// Identity of the caller's `CapabilityToken`; `None` for calls without
+    /// a token or with an anonymous one
+    pub caller: Option<String>,
+    /// Capabilities the method requires, which the caller had to hold
+    pub capabilities: Vec<String>,
+    /// SHA-256 of the input
+    pub input_sha256: [u8; 32],
+    /// FFI error code of the outcome; `ERR_SUCCESS` (0) on success
+    pub code: c_int,
+    /// Time spent in interceptors and the handler; zero for rejected calls
+    pub duration: Duration,
+}
+
+/// Destination of audit records
+///
+/// Called on the thread that made the call, possibly concurrently.
+/// Closures `Fn(&AuditRecord) -> Result<(), RpcError>` are sinks too.
+pub trait AuditSink: Send + Sync {
+    /// Store one record
+    fn record(&self, record: &AuditRecord) -> Result<(), RpcError>;
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&AuditRecord) -> Result<(), RpcError> + Send + Sync,
+{
+    fn record(&self, record: &AuditRecord) -> Result<(), RpcError> {
+        self(record)
+    }
+}
+
+/// MAC-chained audit log file
+///
+/// Each line is a JSON object: `seq` (from 1), `ts_ms` (Unix time in
+/// milliseconds), `method`, `caller`, `capabilities`, `input_sha256`,
+/// `rc`, `duration_us`, then `prev`, the `mac` of the preceding line (64
+/// zeros for the first), and `mac`, the HMAC-SHA256 of the line up to the
+/// comma before `"mac"` under the sink's key. MACs are lowercase hex.
+pub struct FileSink {
+    path: PathBuf,
+    key: Vec<u8>,
+    chain: Mutex<Chain>,
+}
+
+/// Where the next record continues the file
+struct Chain {
+    file: File,
+    seq: u64,
+    last_mac: String,
+}
+
+impl FileSink {
+    /// Open or create an audit log keyed with `key`, continuing its chain
+    ///
+    /// Fails if `key` is empty, or if the file cannot be opened or does not
+    /// pass `verify` with `key`, so records are never appended to a tampered
+    /// log. An existing log must be reopened with the key it was written with.
+    pub fn open(path: impl AsRef<Path>, key: &[u8]) -> Result<Self, RpcError> {
+        let path = path.as_ref();
+        check_key(key)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| io_error(path, e))?;
+
+        let verification = verify(path, key)?;
+        if let Some(broken) = &verification.broken {
+            return Err(RpcError::Internal(format!(
+                "audit log {}: line {}: {}",
+                path.display(),
+                broken.line,
+                broken.reason
+            )));
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            key: key.to_vec(),
+            chain: Mutex::new(Chain {
+                file,
+                seq: verification.records,
+                last_mac: verification.last_mac,
+            }),
+        })
+    }
+
+    /// Path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AuditSink for FileSink {
+    fn record(&self, record: &AuditRecord) -> Result<(), RpcError> {
+        let mut chain = self.chain.lock();
+        let seq = chain.seq + 1;
+
+        let mut line = format!("{{\"seq\":{},", seq);
+        push_fields(&mut line, record);
+        let _ = write!(line, ",\"prev\":\"{}\"", chain.last_mac);
+        let mac = hex(&hmac_sha256(&self.key, line.as_bytes()));
+        let _ = writeln!(line, ",\"mac\":\"{}\"}}", mac);
+
+        // Only advance the chain once the record is on disk
+        chain
+            .file
+            .write_all(line.as_bytes())
+            .and_then(|()| chain.file.sync_data())
+            .map_err(|e| io_error(&self.path, e))?;
+        chain.seq = seq;
+        chain.last_mac = mac;
+        Ok(())
+    }
+}
+
+/// Result of checking an audit log with `verify`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    /// Records that passed, all before the first broken one
+    pub records: u64,
+    /// MAC of the last record that passed (64 zeros for none); compare it
+    /// with a copy kept outside the log to detect truncation
+    pub last_mac: String,
+    /// First record that failed, if any
+    pub broken: Option<Broken>,
+}
+
+impl Verification {
+    /// Whether every record passed
+    pub fn is_intact(&self) -> bool {
+        self.broken.is_none()
+    }
+}
+
+/// A record that failed verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Broken {
+    /// Line number, from 1
+    pub line: u64,
+    /// What is wrong with it
+    pub reason: &'static str,
+}
+
+/// Check the MAC chain of an audit log written by `FileSink` with `key`
+///
+/// Fails only if `key` is empty or the file cannot be read; a missing file
+/// is an empty log. Tampering, or a log written with another key, is
+/// reported in `Verification::broken`. Dropped trailing records are not;
+/// see the module documentation.
+pub fn verify(path: impl AsRef<Path>, key: &[u8]) -> Result<Verification, RpcError> {
+    let path = path.as_ref();
+    check_key(key)?;
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(io_error(path, e)),
+    };
+
+    let mut verification = Verification {
+        records: 0,
+        last_mac: GENESIS.to_string(),
+        broken: None,
+    };
+    // Records end with a newline, so a complete log leaves an empty piece
+    let pieces: Vec<&[u8]> = contents.split(|&b| b == b'\n').collect();
+    let (partial, lines) = pieces.split_last().expect("split yields at least one piece");
+
+    for (i, line) in lines.iter().enumerate() {
+        match check_line(key, line, &verification.last_mac, verification.records + 1) {
+            Ok(mac) => {
+                verification.records += 1;
+                verification.last_mac = mac;
+            }
+            Err(reason) => {
+                verification.broken = Some(Broken {
+                    line: i as u64 + 1,
+                    reason,
+                });
+                return Ok(verification);
+            }
+        }
+    }
+    if !partial.is_empty() {
+        verification.broken = Some(Broken {
+            line: lines.len() as u64 + 1,
+            reason: "not a complete record",
+        });
+    }
+    Ok(verification)
+}
+
+/// Check one line against the preceding MAC, returning its own MAC
+fn check_line(key: &[u8], line: &[u8], prev: &str, seq: u64) -> Result<String, &'static str> {
+    let line = std::str::from_utf8(line).map_err(|_| "not UTF-8")?;
+    let mac_at = line
+        .len()
+        .checked_sub(",\"mac\":\"\"}".len() + MAC_LEN)
+        .filter(|&at| line.is_char_boundary(at))
+        .ok_or("not a complete record")?;
+    let (body, tail) = line.split_at(mac_at);
+
+    let mac = tail
+        .strip_prefix(",\"mac\":\"")
+        .and_then(|tail| tail.strip_suffix("\"}"))
+        .ok_or("not a complete record")?;
+    if !line.starts_with(&format!("{{\"seq\":{},", seq)) {
+        return Err("record out of sequence");
+    }
+    if !body.ends_with(&format!(",\"prev\":\"{}\"", prev)) {
+        return Err("does not continue the preceding record");
+    }
+    if hex(&hmac_sha256(key, body.as_bytes())) != mac {
+        return Err("MAC does not match contents");
+    }
+    Ok(mac.to_string())
+}
+
+/// Refuse an empty key, which would make the chain forgeable
+fn check_key(key: &[u8]) -> Result<(), RpcError> {
+    if key.is_empty() {
+        return Err(RpcError::Internal("audit key is empty".into()));
+    }
+    Ok(())
+}
+
+/// Append the fields of `record` between `seq` and `prev`
+fn push_fields(out: &mut String, record: &AuditRecord) {
+    let ts_ms = record.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let _ = write!(out, "\"ts_ms\":{},\"method\":", ts_ms);
+    push_str(out, Some(&record.method));
+    out.push_str(",\"caller\":");
+    push_str(out, record.caller.as_deref());
+    out.push_str(",\"capabilities\":[");
+    for (i, capability) in record.capabilities.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        push_str(out, Some(capability));
+    }
+    let _ = write!(
+        out,
+        "],\"input_sha256\":\"{}\",\"rc\":{},\"duration_us\":{}",
+        hex(&record.input_sha256),
+        record.code,
+        record.duration.as_micros()
+    );
+}
+
+/// SHA-256 of `bytes`
+pub(crate) fn sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+/// HMAC-SHA256 of `message` under `key` (RFC 2104)
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; BLOCK_LEN];
+    if key.len() > BLOCK_LEN {
+        block[..32].copy_from_slice(&sha256(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let pad = |byte: u8| block.map(|b| b ^ byte);
+    let inner = Sha256::new().chain_update(pad(0x36)).chain_update(message).finalize();
+    Sha256::new().chain_update(pad(0x5c)).chain_update(inner).finalize().into()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(out, "{:02x}", b);
+    }
+    out
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> RpcError {
+    RpcError::Internal(format!("audit log {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CallOptions, CapabilityToken, Registry};
+    use std::sync::Arc;
+
+    fn record(method: &str, code: c_int) -> AuditRecord {
+        AuditRecord {
+            timestamp: UNIX_EPOCH + Duration::from_millis(1_700_000_000_000),
+            method: method.to_string(),
+            caller: Some("script \"a\"".to_string()),
+            capabilities: vec!["graph.write".to_string()],
+            input_sha256: sha256(b"{}"),
+            code,
+            duration: Duration::from_micros(42),
+        }
+    }
+
+    #[test]
+    fn test_registry_audits_flagged_methods() {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&records);
+        let mut registry = Registry::new();
+        registry.register("udg.delete", |_| Ok(vec![])).audited().requires("graph.write");
+        registry.register("udg.get", |input| Ok(input.to_vec()));
+        registry.register("sys.kill", |_| Err(RpcError::NotFound("pid".into())));
+        registry.audit("sys.*");
+        registry.set_audit_sink(Some(Arc::new(move |record: &AuditRecord| {
+            sink.lock().push(record.clone());
+            Ok(())
+        })));
+
+        let script = CapabilityToken::named("script");
+        let options = CallOptions::new().with_capabilities(script.clone());
+        registry.call_with("udg.delete", b"{}", &options).unwrap_err();
+        script.grant("graph.*");
+        registry.call_with("udg.delete", b"{}", &options).unwrap();
+        registry.call("udg.get", b"{}").unwrap();
+        registry.call("sys.kill", b"").unwrap_err();
+
+        let records = records.lock();
+        let summary: Vec<_> = records
+            .iter()
+            .map(|r| (r.method.as_str(), r.caller.as_deref(), r.code))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("udg.delete", Some("script"), crate::ERR_PERMISSION_DENIED),
+                ("udg.delete", Some("script"), crate::ERR_SUCCESS),
+                ("sys.kill", None, crate::ERR_NOT_FOUND),
+            ]
+        );
+        assert_eq!(records[0].capabilities, ["graph.write"]);
+        assert_eq!(records[0].input_sha256, sha256(b"{}"));
+        assert!(registry.describe("sys.kill").unwrap().audited);
+        assert!(!registry.describe("udg.get").unwrap().audited);
+    }
+
+    #[test]
+    fn test_hmac_sha256_matches_rfc_4231() {
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(hex(&mac), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        let mac = hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First");
+        assert_eq!(hex(&mac), "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54");
+    }
+
+    #[test]
+    fn test_file_sink_chain_detects_tampering() {
+        const KEY: &[u8] = b"host secret";
+        let path = std::env::temp_dir().join(format!("rrpc-audit-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let sink = FileSink::open(&path, KEY).unwrap();
+        sink.record(&record("udg.delete", 0)).unwrap();
+        sink.record(&record("udg.delete", 12)).unwrap();
+        drop(sink);
+
+        // Reopening continues the chain
+        FileSink::open(&path, KEY).unwrap().record(&record("udg.purge", 0)).unwrap();
+        let verification = verify(&path, KEY).unwrap();
+        assert!(verification.is_intact(), "{:?}", verification);
+        assert_eq!(verification.records, 3);
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let first = log.lines().next().unwrap();
+        assert!(
+            first.starts_with(r#"{"seq":1,"ts_ms":1700000000000,"method":"udg.delete","caller":"script \"a\"","capabilities":["graph.write"],"input_sha256":"44136fa3"#),
+            "{}",
+            first
+        );
+        assert!(first.contains(&format!(r#","rc":0,"duration_us":42,"prev":"{}","mac":""#, GENESIS)));
+
+        // Without the key the chain can neither be checked nor recomputed
+        assert_eq!(verify(&path, b"guess").unwrap().broken.unwrap().line, 1);
+        assert!(FileSink::open(&path, b"").is_err());
+        let (body, _) = first.rsplit_once(",\"mac\":").unwrap();
+        let forged = format!("{},\"mac\":\"{}\"}}", body, hex(&sha256(body.as_bytes())));
+
+        let broken = |log: String| {
+            std::fs::write(&path, log).unwrap();
+            verify(&path, KEY).unwrap().broken
+        };
+        let lines: Vec<_> = log.lines().collect();
+        let rehashed = broken(format!("{}\n{}\n{}\n", forged, lines[1], lines[2])).unwrap();
+        assert_eq!((rehashed.line, rehashed.reason), (1, "MAC does not match contents"));
+        let edited = broken(log.replacen(r#""rc":12"#, r#""rc":0"#, 1)).unwrap();
+        assert_eq!((edited.line, edited.reason), (2, "MAC does not match contents"));
+        let removed = broken(format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert_eq!((removed.line, removed.reason), (2, "record out of sequence"));
+        assert_eq!(broken(log[..log.len() - 10].to_string()).unwrap().line, 3);
+        assert!(FileSink::open(&path, KEY).is_err());
+
+        // Dropping the newest record only shows against a stored last MAC
+        assert!(broken(format!("{}\n{}\n", lines[0], lines[1])).is_none());
+        assert_ne!(verify(&path, KEY).unwrap().last_mac, verification.last_mac);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(verify(&path, KEY).unwrap().records, 0);
+    }
+}
@@ -0,0 +1,144 @@
+//! Batched calls
+//!
+//! A batch runs many calls in one go, either in order on the calling thread
+//! or spread across scoped threads. With `stop_on_error`, entries that had
+//! not started when a call failed are skipped; in parallel mode calls that
+//! were already running still finish.
+
+use crate::trace;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+/// How a batch is executed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchMode {
+    /// Run entries concurrently instead of one after another
+    pub parallel: bool,
+    /// Skip entries not yet started once any entry fails
+    pub stop_on_error: bool,
+}
+
+impl BatchMode {
+    /// Sequential execution that runs every entry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run entries concurrently
+    pub fn parallel(mut self) -> Self {
+        self.parallel = true;
+        self
+    }
+
+    /// Skip remaining entries after the first failure
+    pub fn stop_on_error(mut self) -> Self {
+        self.stop_on_error = true;
+        self
+    }
+}
+
+/// Run `len` calls, returning `None` for entries skipped by `stop_on_error`
+pub(crate) fn run<T, E, F>(len: usize, mode: BatchMode, call: F) -> Vec<Option<Result<T, E>>>
+where
+    T: Send,
+    E: Send,
+    F: Fn(usize) -> Result<T, E> + Sync,
+{
+    let threads = if mode.parallel {
+        thread::available_parallelism().map_or(4, |n| n.get()).min(len)
+    } else {
+        1
+    };
+
+    if threads <= 1 {
+        let mut failed = false;
+        return (0..len)
+            .map(|i| {
+                if failed && mode.stop_on_error {
+                    return None;
+                }
+                let result = call(i);
+                failed |= result.is_err();
+                Some(result)
+            })
+            .collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let mut results: Vec<Option<Result<T, E>>> = (0..len).map(|_| None).collect();
+
+    let finished: Vec<Vec<(usize, Result<T, E>)>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(trace::in_current_span(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= len || (mode.stop_on_error && failed.load(Ordering::Acquire)) {
+                            break;
+                        }
+                        let result = call(i);
+                        if result.is_err() {
+                            failed.store(true, Ordering::Release);
+                        }
+                        done.push((i, result));
+                    }
+                    done
+                }))
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|w| w.join().expect("batch worker panicked"))
+            .collect()
+    });
+
+    for (i, result) in finished.into_iter().flatten() {
+        results[i] = Some(result);
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fail_on_three(i: usize) -> Result<usize, usize> {
+        if i == 3 {
+            Err(i)
+        } else {
+            Ok(i * 10)
+        }
+    }
+
+    #[test]
+    fn test_sequential_runs_every_entry() {
+        let results = run(6, BatchMode::new(), fail_on_three);
+
+        assert_eq!(results.len(), 6);
+        assert_eq!(results[2], Some(Ok(20)));
+        assert_eq!(results[3], Some(Err(3)));
+        assert_eq!(results[5], Some(Ok(50)));
+    }
+
+    #[test]
+    fn test_sequential_stop_on_error_skips_rest() {
+        let results = run(6, BatchMode::new().stop_on_error(), fail_on_three);
+
+        assert_eq!(results[2], Some(Ok(20)));
+        assert_eq!(results[3], Some(Err(3)));
+        assert!(results[4..].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_parallel_keeps_entry_order() {
+        let results = run(100, BatchMode::new().parallel(), Ok::<_, ()>);
+
+        for (i, result) in results.into_iter().enumerate() {
+            assert_eq!(result, Some(Ok(i)));
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Capability-based permissions
+//!
+//! A method can require capabilities, named by dotted strings such as
+//! `process.terminate`: declared at registration with
+//! `MethodConfig::requires`, or imposed on a group of methods (for example
+//! everything a plugin provides) with `Registry::require`. A call is only
+//! dispatched if every required capability is granted by the
+//! `CapabilityToken` in its `CallOptions` or, for calls without one, by the
+//! registry's default grants (`Registry::grant_default`). Otherwise it fails
+//! with `RpcError::PermissionDenied`.
+//!
+//! Nested calls made through `CallContext::call` carry the caller's token,
+//! so a permitted method cannot be used to reach one the caller may not
+//! call directly. Methods that require nothing are callable by everyone.
+//!
+//! Grants can be patterns: `"*"` grants every capability and
+//! `"process.*"` every capability starting with `process.`.
+//!
+//! # Example
+//! ```
+//! use rrpc_core::{CallOptions, CapabilityToken, Registry, RpcError};
+//!
+//! let mut registry = Registry::new();
+//! registry.register("process.kill", |_| Ok(vec![])).requires("process.terminate");
+//!
+//! let script = CapabilityToken::new();
+//! script.grant("ui.*");
+//! let options = CallOptions::new().with_capabilities(script);
+//! assert!(matches!(registry.call_with("process.kill", b"", &options), Err(RpcError::PermissionDenied(_))));
+//!
+//! let admin = CapabilityToken::new();
+//! admin.grant("process.*");
+//! assert!(registry.call_with("process.kill", b"", &CallOptions::new().with_capabilities(admin)).is_ok());
+//! ```
+
+use crate::interceptor::Scope;
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// Set of capabilities granted to a caller
+///
+/// Clones share the same set, so grants made later apply to every clone,
+/// including calls already holding the token.
+#[derive(Clone, Debug, Default)]
+pub struct CapabilityToken(Arc<Grants>);
+
+#[derive(Debug, Default)]
+struct Grants {
+    identity: Option<String>,
+    scopes: RwLock<Vec<Scope>>,
+}
+
+impl CapabilityToken {
+    /// Create a token that grants nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a token that grants nothing, identifying its holder
+    ///
+    /// The identity is recorded in the audit log for calls made with the
+    /// token; see the `audit` module.
+    pub fn named(identity: impl Into<String>) -> Self {
+        Self(Arc::new(Grants {
+            identity: Some(identity.into()),
+            scopes: RwLock::default(),
+        }))
+    }
+
+    /// Identity of the holder, if the token was created with `named`
+    pub fn identity(&self) -> Option<&str> {
+        self.0.identity.as_deref()
+    }
+
+    /// Grant a capability, or every capability matching a `*` pattern
+    pub fn grant(&self, capability: &str) {
+        let scope = Scope::parse(capability);
+        let mut granted = self.0.scopes.write();
+        if !granted.contains(&scope) {
+            granted.push(scope);
+        }
+    }
+
+    /// Whether `capability` has been granted
+    pub fn allows(&self, capability: &str) -> bool {
+        self.0.scopes.read().iter().any(|scope| scope.matches(capability))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grants_and_patterns() {
+        let token = CapabilityToken::new();
+        let clone = token.clone();
+        assert!(!clone.allows("fs.read"));
+
+        token.grant("fs.read");
+        token.grant("process.*");
+        assert!(clone.allows("fs.read"));
+        assert!(!clone.allows("fs.write"));
+        assert!(clone.allows("process.terminate"));
+
+        token.grant("*");
+        assert!(clone.allows("fs.write"));
+    }
+}
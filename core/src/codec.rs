@@ -0,0 +1,107 @@
+//! Serde codecs for typed handlers
+//!
+//! A `Codec` turns request and response types into the bytes carried by
+//! `rrpc_call`. `Registry::register_typed` uses one to wrap a typed handler;
+//! decode failures become `RpcError::ParseError` and encode failures
+//! `RpcError::SerializationError`.
+//!
+//! Built-in codecs are enabled by cargo features: `json` (default),
+//! `msgpack` and `cbor`.
+
+use crate::error::RpcError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Wire format for typed handlers
+pub trait Codec {
+    /// Serialise a response
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, RpcError>;
+
+    /// Deserialise a request
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, RpcError>;
+}
+
+/// JSON via `serde_json`
+#[cfg(feature = "json")]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Codec for Json {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, RpcError> {
+        serde_json::to_vec(value).map_err(|e| RpcError::SerializationError(format!("json: {}", e)))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, RpcError> {
+        serde_json::from_slice(bytes).map_err(|e| RpcError::ParseError(format!("json: {}", e)))
+    }
+}
+
+/// MessagePack via `rmp-serde`, with structs encoded as maps
+#[cfg(feature = "msgpack")]
+pub struct MsgPack;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MsgPack {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, RpcError> {
+        rmp_serde::to_vec_named(value).map_err(|e| RpcError::SerializationError(format!("msgpack: {}", e)))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, RpcError> {
+        rmp_serde::from_slice(bytes).map_err(|e| RpcError::ParseError(format!("msgpack: {}", e)))
+    }
+}
+
+/// CBOR via `ciborium`
+#[cfg(feature = "cbor")]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, RpcError> {
+        let mut out = Vec::new();
+        ciborium::into_writer(value, &mut out).map_err(|e| RpcError::SerializationError(format!("cbor: {}", e)))?;
+        Ok(out)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, RpcError> {
+        ciborium::from_reader(bytes).map_err(|e| RpcError::ParseError(format!("cbor: {}", e)))
+    }
+}
+
+#[cfg(all(test, any(feature = "json", feature = "msgpack", feature = "cbor")))]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    fn round_trip<C: Codec>() {
+        let point = Point { x: 3, y: -4 };
+        let bytes = C::encode(&point).unwrap();
+        assert_eq!(C::decode::<Point>(&bytes).unwrap(), point);
+        assert!(matches!(C::decode::<Point>(&[0xc1]), Err(RpcError::ParseError(_))));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_round_trip() {
+        round_trip::<Json>();
+        assert_eq!(Json::encode(&Point { x: 1, y: 2 }).unwrap(), br#"{"x":1,"y":2}"#);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_round_trip() {
+        round_trip::<MsgPack>();
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_round_trip() {
+        round_trip::<Cbor>();
+    }
+}
@@ -0,0 +1,143 @@
+//! Per-call context handed to handlers
+
+use crate::cancel::CancelToken;
+use crate::capability::CapabilityToken;
+use crate::error::RpcError;
+use crate::registry::Registry;
+use std::time::{Duration, Instant};
+
+/// Options for a single call
+#[derive(Clone, Debug, Default)]
+pub struct CallOptions {
+    /// Token the caller can use to cancel the call
+    pub cancel: Option<CancelToken>,
+    /// Point in time after which the call fails with `RpcError::Timeout`
+    pub deadline: Option<Instant>,
+    /// Capabilities of the caller; `None` uses the registry's default grants
+    pub capabilities: Option<CapabilityToken>,
+}
+
+impl CallOptions {
+    /// Options with nothing set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach a cancellation token
+    pub fn with_cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Call with the capabilities granted to `token`
+    pub fn with_capabilities(mut self, token: CapabilityToken) -> Self {
+        self.capabilities = Some(token);
+        self
+    }
+
+    /// Set an absolute deadline, keeping an earlier one if already set
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(self.deadline.map_or(deadline, |d| d.min(deadline)));
+        self
+    }
+
+    /// Limit the call to `timeout` from now, keeping an earlier deadline
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.with_deadline(deadline),
+            None => self,
+        }
+    }
+
+    /// Whether the call has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+
+    /// Whether the deadline has passed
+    pub fn is_expired(&self) -> bool {
+        self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    /// Fail with `Cancelled` or `Timeout` if the call should stop
+    pub(crate) fn check(&self) -> Result<(), RpcError> {
+        if self.is_cancelled() {
+            Err(RpcError::Cancelled)
+        } else if self.is_expired() {
+            Err(RpcError::Timeout)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Context of an in-flight call
+///
+/// Handlers registered with `Registry::register_with_ctx` receive it and
+/// can use it to invoke other methods of the same registry.
+pub struct CallContext<'a> {
+    registry: &'a Registry,
+    method: &'a str,
+    depth: usize,
+    options: &'a CallOptions,
+}
+
+impl<'a> CallContext<'a> {
+    pub(crate) fn new(registry: &'a Registry, method: &'a str, depth: usize, options: &'a CallOptions) -> Self {
+        Self {
+            registry,
+            method,
+            depth,
+            options,
+        }
+    }
+
+    /// Name of the method being executed
+    pub fn method(&self) -> &str {
+        self.method
+    }
+
+    /// Nesting depth of this call (0 for a call made by the host)
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Whether the caller has cancelled this call
+    pub fn is_cancelled(&self) -> bool {
+        self.options.is_cancelled()
+    }
+
+    /// Return `RpcError::Cancelled` if the caller has cancelled this call,
+    /// or `RpcError::Timeout` if its deadline has passed
+    ///
+    /// Convenient for polling inside loops: `ctx.check_cancelled()?;`
+    pub fn check_cancelled(&self) -> Result<(), RpcError> {
+        self.options.check()
+    }
+
+    /// Deadline of this call, from the caller or the method's timeout
+    pub fn deadline(&self) -> Option<Instant> {
+        self.options.deadline
+    }
+
+    /// Time left before the deadline (zero once it has passed)
+    pub fn remaining(&self) -> Option<Duration> {
+        self.options
+            .deadline
+            .map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    /// The caller's cancellation token, if one was supplied
+    pub fn cancel_token(&self) -> Option<&CancelToken> {
+        self.options.cancel.as_ref()
+    }
+
+    /// Call another registered method from inside a handler
+    ///
+    /// The nested call shares this call's cancellation token and deadline. Fails with
+    /// `RpcError::Internal` once the registry's maximum call depth is
+    /// exceeded.
+    pub fn call(&self, method: &str, input: &[u8]) -> Result<Vec<u8>, RpcError> {
+        self.registry.dispatch(method, input, self.depth + 1, self.options)
+    }
+}
@@ -19,6 +19,18 @@ pub enum RpcError {
     
     /// Internal error
     Internal(String),
+
+    /// Handler panicked; carries the panic message
+    Panic(String),
+
+    /// Call was cancelled by the caller
+    Cancelled,
+
+    /// Call did not finish before its deadline
+    Timeout,
+
+    /// Caller lacks a capability the method requires
+    PermissionDenied(String),
 }
 
 impl fmt::Display for RpcError {
@@ -29,6 +41,27 @@ impl fmt::Display for RpcError {
             RpcError::ParseError(e) => write!(f, "Parse error: {}", e),
             RpcError::SerializationError(e) => write!(f, "Serialization error: {}", e),
             RpcError::Internal(e) => write!(f, "Internal error: {}", e),
+            RpcError::Panic(m) => write!(f, "Handler panicked: {}", m),
+            RpcError::Cancelled => write!(f, "Call cancelled"),
+            RpcError::Timeout => write!(f, "Call timed out"),
+            RpcError::PermissionDenied(m) => write!(f, "Permission denied: {}", m),
+        }
+    }
+}
+
+impl RpcError {
+    /// Short, stable name of the error variant, e.g. `"parse_error"`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RpcError::UnknownMethod(_) => "unknown_method",
+            RpcError::NotFound(_) => "not_found",
+            RpcError::ParseError(_) => "parse_error",
+            RpcError::SerializationError(_) => "serialization",
+            RpcError::Internal(_) => "internal",
+            RpcError::Panic(_) => "panic",
+            RpcError::Cancelled => "cancelled",
+            RpcError::Timeout => "timeout",
+            RpcError::PermissionDenied(_) => "permission_denied",
         }
     }
 }
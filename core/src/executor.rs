@@ -0,0 +1,86 @@
+//! Minimal executor for driving handler futures to completion
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// Wakes the thread blocked in `block_on`
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Run a future to completion on the current thread
+///
+/// Futures driven by an external reactor (e.g. a tokio runtime running on
+/// other threads) work as long as they wake their `Waker`.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Future that completes after a background thread wakes it
+    struct Delayed {
+        spawned: bool,
+        done: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl Future for Delayed {
+        type Output = u32;
+
+        fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+            use std::sync::atomic::Ordering;
+
+            if self.done.load(Ordering::SeqCst) {
+                return Poll::Ready(7);
+            }
+            if !self.spawned {
+                self.spawned = true;
+                let done = Arc::clone(&self.done);
+                let waker = cx.waker().clone();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(10));
+                    done.store(true, Ordering::SeqCst);
+                    waker.wake();
+                });
+            }
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn test_block_on_ready() {
+        assert_eq!(block_on(async { 1 + 1 }), 2);
+    }
+
+    #[test]
+    fn test_block_on_woken_from_other_thread() {
+        let fut = Delayed {
+            spawned: false,
+            done: Arc::default(),
+        };
+        assert_eq!(block_on(fut), 7);
+    }
+}
@@ -0,0 +1,178 @@
+//! Interceptors around method calls
+//!
+//! An interceptor wraps every call of the methods it applies to. It is
+//! given the call context, the input and a `Next` for the rest of the
+//! chain, and decides what to do with them: pass the input on unchanged
+//! or transformed, inspect or rewrite the result, time the call, or return
+//! early without calling the handler at all.
+//!
+//! Interceptors run in registration order, the first registered being the
+//! outermost. Each applies to a scope: `"*"` for every method, a prefix
+//! such as `"udg.*"` for every method starting with `udg.`, or an exact
+//! method name.
+//!
+//! # Example
+//! ```
+//! use rrpc_core::{Registry, RpcError};
+//!
+//! let mut registry = Registry::new();
+//! registry.register("udg.echo", |input| Ok(input.to_vec()));
+//!
+//! registry.intercept("udg.*", |_ctx, input, next| {
+//!     if input.is_empty() {
+//!         return Err(RpcError::ParseError("empty input".into()));
+//!     }
+//!     next.run(input)
+//! });
+//!
+//! assert_eq!(registry.call("udg.echo", b"hi").unwrap(), b"hi");
+//! assert!(registry.call("udg.echo", b"").is_err());
+//! ```
+
+use crate::context::CallContext;
+use crate::error::RpcError;
+use std::sync::Arc;
+
+/// Interceptor function type: (call context, input bytes, rest of the chain) → result
+pub type Interceptor = Arc<dyn Fn(&CallContext<'_>, &[u8], Next<'_>) -> Result<Vec<u8>, RpcError> + Send + Sync>;
+
+/// Innermost step of a chain: the method's handler applied to the final input
+pub(crate) type Terminal<'a> = dyn FnMut(&[u8]) -> Result<Vec<u8>, RpcError> + 'a;
+
+/// Names matched by a pattern: `"*"`, a prefix ending in `*`, or an exact name
+///
+/// Used for interceptor scopes and capability patterns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Scope {
+    All,
+    Prefix(String),
+    Exact(String),
+}
+
+impl Scope {
+    pub(crate) fn parse(pattern: &str) -> Self {
+        match pattern {
+            "*" => Scope::All,
+            _ => match pattern.strip_suffix('*') {
+                Some(prefix) => Scope::Prefix(prefix.to_string()),
+                None => Scope::Exact(pattern.to_string()),
+            },
+        }
+    }
+
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Prefix(prefix) => name.starts_with(prefix.as_str()),
+            Scope::Exact(exact) => name == exact,
+        }
+    }
+}
+
+/// An interceptor and the methods it applies to
+#[derive(Clone)]
+pub(crate) struct Layer {
+    scope: Scope,
+    interceptor: Interceptor,
+}
+
+impl Layer {
+    pub(crate) fn new(pattern: &str, interceptor: Interceptor) -> Self {
+        Self {
+            scope: Scope::parse(pattern),
+            interceptor,
+        }
+    }
+}
+
+/// The rest of an interceptor chain, ending in the method's handler
+///
+/// Consumed by `run`, so an interceptor invokes the rest of the chain at
+/// most once.
+pub struct Next<'a> {
+    chain: &'a [Layer],
+    ctx: &'a CallContext<'a>,
+    handler: &'a mut Terminal<'a>,
+}
+
+impl<'a> Next<'a> {
+    /// Start of the chain for a call
+    pub(crate) fn new(
+        chain: &'a [Layer],
+        ctx: &'a CallContext<'a>,
+        handler: &'a mut Terminal<'a>,
+    ) -> Self {
+        Self { chain, ctx, handler }
+    }
+
+    /// Run the remaining interceptors and the handler with `input`
+    pub fn run(self, input: &[u8]) -> Result<Vec<u8>, RpcError> {
+        let method = self.ctx.method();
+        let mut chain = self.chain;
+        while let Some((layer, rest)) = chain.split_first() {
+            if layer.scope.matches(method) {
+                let next = Next {
+                    chain: rest,
+                    ctx: self.ctx,
+                    handler: self.handler,
+                };
+                return (layer.interceptor)(self.ctx, input, next);
+            }
+            chain = rest;
+        }
+        (self.handler)(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Registry;
+    use parking_lot::Mutex;
+
+    #[test]
+    fn test_scope_patterns() {
+        assert!(Scope::parse("*").matches("anything"));
+        assert!(Scope::parse("udg.*").matches("udg.list_nodes"));
+        assert!(!Scope::parse("udg.*").matches("udgx.list"));
+        assert!(Scope::parse("udg.get").matches("udg.get"));
+        assert!(!Scope::parse("udg.get").matches("udg.get_all"));
+    }
+
+    #[test]
+    fn test_chain_order_and_scopes() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = Registry::new();
+        registry.register("udg.echo", |input| Ok(input.to_vec()));
+        registry.register("other", |input| Ok(input.to_vec()));
+
+        let log = Arc::clone(&seen);
+        registry.intercept("*", move |ctx, input, next| {
+            log.lock().push(format!("outer {}", ctx.method()));
+            let mut output = next.run(input)?;
+            output.push(b'!');
+            Ok(output)
+        });
+        let log = Arc::clone(&seen);
+        registry.intercept("udg.*", move |_ctx, input, next| {
+            log.lock().push("inner".to_string());
+            next.run(&input.to_ascii_uppercase())
+        });
+
+        assert_eq!(registry.call("udg.echo", b"hi").unwrap(), b"HI!");
+        assert_eq!(registry.call("other", b"hi").unwrap(), b"hi!");
+        assert_eq!(*seen.lock(), ["outer udg.echo", "inner", "outer other"]);
+    }
+
+    #[test]
+    fn test_short_circuit_skips_handler_and_catches_panics() {
+        let mut registry = Registry::new();
+        registry.register("secret", |_| panic!("handler must not run"));
+        registry.register("boom", |input| Ok(input.to_vec()));
+        registry.intercept("secret", |_ctx, _input, _next| Err(RpcError::Internal("denied".into())));
+        registry.intercept("boom", |_ctx, _input, _next| panic!("interceptor failed"));
+
+        assert!(matches!(registry.call("secret", b""), Err(RpcError::Internal(m)) if m == "denied"));
+        assert!(matches!(registry.call("boom", b""), Err(RpcError::Panic(m)) if m == "interceptor failed"));
+    }
+}
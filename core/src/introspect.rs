@@ -0,0 +1,132 @@
+//! Method introspection
+//!
+//! `MethodInfo` describes a registered method together with the optional
+//! metadata supplied at registration. `to_json` encodes a list of them for
+//! hosts, which read it through `rrpc_list_methods`.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Description of a registered method
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodInfo {
+    /// Method name
+    pub name: String,
+    /// Whether the method is server-streaming
+    pub streaming: bool,
+    /// Human-readable description
+    pub description: Option<String>,
+    /// Name of the input type
+    pub input_type: Option<String>,
+    /// Name of the output type
+    pub output_type: Option<String>,
+    /// Whether callers should migrate away from the method
+    pub deprecated: bool,
+    /// Default timeout of every call
+    pub timeout: Option<Duration>,
+    /// Capabilities a caller needs, declared by the method or imposed by `Registry::require`
+    pub requires: Vec<String>,
+    /// Whether calls are recorded in the audit log
+    pub audited: bool,
+}
+
+/// Optional metadata attached to a method at registration
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Metadata {
+    pub(crate) description: Option<String>,
+    pub(crate) input_type: Option<String>,
+    pub(crate) output_type: Option<String>,
+    pub(crate) deprecated: bool,
+}
+
+/// Encode method descriptions as a JSON array
+///
+/// Each element has the keys `name`, `kind` (`"unary"` or `"stream"`),
+/// `description`, `input_type`, `output_type`, `deprecated`,
+/// `timeout_ms`, `requires` (an array of capabilities) and `audited`;
+/// missing values are `null`.
+pub fn to_json(methods: &[MethodInfo]) -> String {
+    let mut out = String::from("[");
+    for (i, info) in methods.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"name\":");
+        push_str(&mut out, Some(&info.name));
+        out.push_str(",\"kind\":");
+        out.push_str(if info.streaming { "\"stream\"" } else { "\"unary\"" });
+        out.push_str(",\"description\":");
+        push_str(&mut out, info.description.as_deref());
+        out.push_str(",\"input_type\":");
+        push_str(&mut out, info.input_type.as_deref());
+        out.push_str(",\"output_type\":");
+        push_str(&mut out, info.output_type.as_deref());
+        let _ = write!(out, ",\"deprecated\":{}", info.deprecated);
+        match info.timeout {
+            Some(timeout) => {
+                let _ = write!(out, ",\"timeout_ms\":{}", timeout.as_millis());
+            }
+            None => out.push_str(",\"timeout_ms\":null"),
+        }
+        out.push_str(",\"requires\":[");
+        for (j, capability) in info.requires.iter().enumerate() {
+            if j > 0 {
+                out.push(',');
+            }
+            push_str(&mut out, Some(capability));
+        }
+        let _ = write!(out, "],\"audited\":{}}}", info.audited);
+    }
+    out.push(']');
+    out
+}
+
+/// Append a JSON string literal, or `null`
+pub(crate) fn push_str(out: &mut String, value: Option<&str>) {
+    let Some(value) = value else {
+        out.push_str("null");
+        return;
+    };
+
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json_escapes_and_nulls() {
+        let info = MethodInfo {
+            name: "udg.get".into(),
+            streaming: false,
+            description: Some("say \"hi\"\n".into()),
+            input_type: None,
+            output_type: Some("Node".into()),
+            deprecated: true,
+            timeout: Some(Duration::from_millis(250)),
+            requires: vec!["graph.read".into()],
+            audited: false,
+        };
+
+        assert_eq!(
+            to_json(&[info]),
+            r#"[{"name":"udg.get","kind":"unary","description":"say \"hi\"\n","input_type":null,"output_type":"Node","deprecated":true,"timeout_ms":250,"requires":["graph.read"],"audited":false}]"#
+        );
+        assert_eq!(to_json(&[]), "[]");
+    }
+}
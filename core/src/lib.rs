@@ -15,19 +15,77 @@
 //! registry.register("echo", echo);
 //! ```
 
-use parking_lot::Mutex;
+use std::cell::RefCell;
 use std::ffi::CStr;
-use std::os::raw::{c_char, c_int};
-use std::sync::OnceLock;
+use std::os::raw::{c_char, c_int, c_void};
+use parking_lot::{Mutex, RwLock};
+use std::collections::BTreeMap;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::Duration;
+use trace::CallSpan;
 
+pub mod audit;
+pub mod batch;
+pub mod cancel;
+pub mod capability;
+pub mod codec;
+pub mod context;
 pub mod error;
+pub mod executor;
+pub mod interceptor;
+pub mod introspect;
+mod logging;
+pub mod plugin;
+mod pool;
+#[cfg(feature = "macros")]
+pub mod registration;
 pub mod registry;
+pub mod runtime;
+pub mod stats;
+pub mod stream;
+mod trace;
 
+pub use batch::BatchMode;
+pub use cancel::CancelToken;
+pub use capability::CapabilityToken;
+pub use context::{CallContext, CallOptions};
 pub use error::RpcError;
+pub use interceptor::Next;
+pub use introspect::MethodInfo;
 pub use registry::Registry;
+pub use runtime::Runtime;
+pub use stream::StreamSink;
+#[cfg(feature = "macros")]
+pub use rrpc_macros::method;
 
-/// Global registry instance
-static GLOBAL_REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+// Lets `#[method]` expansions inside this crate refer to `::rrpc_core`
+#[cfg(feature = "macros")]
+extern crate self as rrpc_core;
+
+/// Items used by macro expansions; not public API
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "macros")]
+    pub use inventory;
+}
+
+/// Default runtime used by the handle-less `rrpc_*` functions
+///
+/// Created by `rrpc_init` and taken out again by `rrpc_shutdown`; calls
+/// hold their own reference for their whole duration.
+static DEFAULT_RUNTIME: RwLock<Option<Arc<Runtime>>> = RwLock::new(None);
+
+/// Output buffers handed to the host and not yet passed to `rrpc_free`,
+/// by address, with the length and capacity needed to rebuild them
+static LIVE_BUFFERS: Mutex<BTreeMap<usize, (usize, usize)>> = Mutex::new(BTreeMap::new());
+
+/// Returned for empty outputs so successful calls never yield null
+static EMPTY_OUTPUT: u8 = 0;
+
+/// Maximum accepted input size for a single call
+const MAX_INPUT_LEN: usize = 10 * 1024 * 1024; // 10 MB
 
 /// Error codes returned to FFI callers
 pub const ERR_SUCCESS: c_int = 0;
@@ -39,19 +97,303 @@ pub const ERR_SERIALIZATION: c_int = 5;
 pub const ERR_INTERNAL: c_int = 99;
 /// Error returned when input is too large for safety
 pub const ERR_TOO_LARGE: c_int = 6;
+/// Error returned when a handler panicked (the panic was caught)
+pub const ERR_PANIC: c_int = 7;
+/// Error returned when a call was cancelled through its token
+pub const ERR_CANCELLED: c_int = 8;
+/// Error returned when a call did not finish before its deadline
+pub const ERR_TIMEOUT: c_int = 9;
+/// Error returned by `rrpc_call_into` when the caller's buffer is too small
+pub const ERR_BUFFER_TOO_SMALL: c_int = 10;
+/// Error returned when a plugin cannot be loaded or unloaded
+pub const ERR_PLUGIN: c_int = 11;
+/// Error returned when the caller lacks a capability the method requires
+pub const ERR_PERMISSION_DENIED: c_int = 12;
+
+/// Log levels passed to the log callback and `rrpc_set_log_level`
+pub const LOG_OFF: c_int = 0;
+pub const LOG_ERROR: c_int = 1;
+pub const LOG_WARN: c_int = 2;
+pub const LOG_INFO: c_int = 3;
+pub const LOG_DEBUG: c_int = 4;
+pub const LOG_TRACE: c_int = 5;
+
+/// Major ABI version; bumped when an export is removed or changes signature or meaning
+pub const ABI_VERSION_MAJOR: u16 = 1;
+/// Minor ABI version; bumped when exports or feature bits are added
+pub const ABI_VERSION_MINOR: u16 = 8;
+
+/// `rrpc_last_error` / `rrpc_clear_error`
+pub const FEATURE_LAST_ERROR: u64 = 1 << 0;
+/// Runtime handles (`rrpc_runtime_new` and the `_rt` variants)
+pub const FEATURE_RUNTIMES: u64 = 1 << 1;
+/// `rrpc_call_async`
+pub const FEATURE_ASYNC: u64 = 1 << 2;
+/// `rrpc_call_stream`
+pub const FEATURE_STREAMING: u64 = 1 << 3;
+/// Cancellation tokens and `rrpc_call_with_token`
+pub const FEATURE_CANCELLATION: u64 = 1 << 4;
+/// `rrpc_call_with_timeout` and per-method timeouts
+pub const FEATURE_TIMEOUTS: u64 = 1 << 5;
+/// `rrpc_call_into`
+pub const FEATURE_CALL_INTO: u64 = 1 << 6;
+/// `rrpc_call_batch`
+pub const FEATURE_BATCH: u64 = 1 << 7;
+/// `rrpc_list_methods` / `rrpc_has_method`
+pub const FEATURE_INTROSPECTION: u64 = 1 << 8;
+/// `rrpc_shutdown` and re-initialisation with `rrpc_init`
+pub const FEATURE_LIFECYCLE: u64 = 1 << 9;
+/// `rrpc_register_host`
+pub const FEATURE_HOST_METHODS: u64 = 1 << 10;
+/// `rrpc_load_plugin` / `rrpc_unload_plugin`
+pub const FEATURE_PLUGINS: u64 = 1 << 11;
+/// `rrpc_stats`
+pub const FEATURE_STATS: u64 = 1 << 12;
+/// `rrpc_set_log_callback` / `rrpc_set_log_level`
+pub const FEATURE_LOG_CALLBACK: u64 = 1 << 13;
+/// Capability tokens, `rrpc_grant` and `ERR_PERMISSION_DENIED` (1.6)
+pub const FEATURE_CAPABILITIES: u64 = 1 << 14;
+/// MAC-chained audit log and `rrpc_audit_verify` (1.7)
+pub const FEATURE_AUDIT: u64 = 1 << 15;
+/// `rrpc_call_batch_with_capabilities` (1.8)
+pub const FEATURE_BATCH_CAPABILITIES: u64 = 1 << 16;
+
+/// Every feature implemented by this build
+const FEATURES: u64 = FEATURE_LAST_ERROR
+    | FEATURE_RUNTIMES
+    | FEATURE_ASYNC
+    | FEATURE_STREAMING
+    | FEATURE_CANCELLATION
+    | FEATURE_TIMEOUTS
+    | FEATURE_CALL_INTO
+    | FEATURE_BATCH
+    | FEATURE_INTROSPECTION
+    | FEATURE_LIFECYCLE
+    | FEATURE_HOST_METHODS
+    | FEATURE_PLUGINS
+    | FEATURE_STATS
+    | FEATURE_LOG_CALLBACK
+    | FEATURE_CAPABILITIES
+    | FEATURE_AUDIT
+    | FEATURE_BATCH_CAPABILITIES;
+
+thread_local! {
+    /// Error of the most recent failing call on this thread
+    static LAST_ERROR: RefCell<Option<RpcError>> = const { RefCell::new(None) };
+}
+
+/// Map an `RpcError` to its FFI error code
+pub fn error_code(err: &RpcError) -> c_int {
+    match err {
+        RpcError::UnknownMethod(_) => ERR_UNKNOWN_METHOD,
+        RpcError::NotFound(_) => ERR_NOT_FOUND,
+        RpcError::ParseError(_) => ERR_PARSE_ERROR,
+        RpcError::SerializationError(_) => ERR_SERIALIZATION,
+        RpcError::Internal(_) => ERR_INTERNAL,
+        RpcError::Panic(_) => ERR_PANIC,
+        RpcError::Cancelled => ERR_CANCELLED,
+        RpcError::Timeout => ERR_TIMEOUT,
+        RpcError::PermissionDenied(_) => ERR_PERMISSION_DENIED,
+    }
+}
+
+/// Get the error of the last failing FFI call on the current thread, if any
+///
+/// The slot is overwritten by every failing call and is not reset by
+/// successful ones; use `clear_last_error` to reset it explicitly.
+pub fn last_error() -> Option<RpcError> {
+    LAST_ERROR.with(|slot| slot.borrow().clone())
+}
+
+/// Reset the current thread's last-error slot
+pub fn clear_last_error() {
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = None);
+}
+
+/// Record `err` as the current thread's last error and return `code`
+fn fail(code: c_int, err: RpcError) -> c_int {
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = Some(err));
+    code
+}
+
+/// Hand `data`'s allocation to the caller without copying
+///
+/// The buffer is leaked as is, whatever its spare capacity, and its length
+/// and capacity are recorded in `LIVE_BUFFERS` so `rrpc_free` can rebuild
+/// it. Empty outputs point at `EMPTY_OUTPUT`, which `rrpc_free` ignores.
+unsafe fn write_output(data: Vec<u8>, out_ptr: *mut *mut u8, out_len: *mut usize) -> c_int {
+    let len = data.len();
+    *out_ptr = if len == 0 {
+        empty_output()
+    } else {
+        let mut data = ManuallyDrop::new(data);
+        let ptr = data.as_mut_ptr();
+        LIVE_BUFFERS.lock().insert(ptr as usize, (len, data.capacity()));
+        ptr
+    };
+    *out_len = len;
+
+    ERR_SUCCESS
+}
+
+/// Validate and borrow the method name and input of an FFI call
+unsafe fn call_args<'a>(
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+) -> Result<(&'a str, &'a [u8]), c_int> {
+    parse_args(method_ptr, in_ptr, in_len).map_err(|(code, err)| fail(code, err))
+}
+
+/// Validate the arguments of an FFI call without recording an error
+unsafe fn parse_args<'a>(
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+) -> Result<(&'a str, &'a [u8]), (c_int, RpcError)> {
+    if method_ptr.is_null() {
+        return Err((ERR_PARSE_ERROR, RpcError::ParseError("method name is null".into())));
+    }
+
+    if in_len > 0 && in_ptr.is_null() {
+        return Err((ERR_PARSE_ERROR, RpcError::ParseError("input pointer is null".into())));
+    }
+
+    if in_len > MAX_INPUT_LEN {
+        return Err((
+            ERR_TOO_LARGE,
+            RpcError::ParseError(format!("input of {} bytes exceeds limit of {}", in_len, MAX_INPUT_LEN)),
+        ));
+    }
+
+    let method = CStr::from_ptr(method_ptr)
+        .to_str()
+        .map_err(|e| (ERR_PARSE_ERROR, RpcError::ParseError(format!("method name: {}", e))))?;
+
+    let input = if in_len == 0 {
+        &[][..]
+    } else {
+        std::slice::from_raw_parts(in_ptr, in_len)
+    };
+
+    Ok((method, input))
+}
+
+/// Resolve a runtime handle, recording an error for null handles
+unsafe fn runtime_ref<'a>(rt: *const Runtime) -> Result<&'a Runtime, c_int> {
+    rt.as_ref()
+        .ok_or_else(|| fail(ERR_NOT_INITIALIZED, RpcError::Internal("runtime not initialized".into())))
+}
+
+/// Handle of the default runtime, or null if it is not initialized
+///
+/// The handle is only valid while `rt` is alive, so handle-less exports
+/// keep the `Arc` in a local for the duration of the call.
+fn default_handle(rt: &Option<Arc<Runtime>>) -> *const Runtime {
+    rt.as_ref().map_or(std::ptr::null(), Arc::as_ptr)
+}
+
+/// ABI version implemented by this library, as `(major << 16) | minor`
+///
+/// A host built against `major.minor` can use this library if the major
+/// versions are equal and the library's minor version is at least its own.
+/// May be called before `rrpc_init`.
+#[no_mangle]
+pub extern "C" fn rrpc_abi_version() -> u32 {
+    (u32::from(ABI_VERSION_MAJOR) << 16) | u32::from(ABI_VERSION_MINOR)
+}
+
+/// Bitmask of the optional features implemented by this library
+///
+/// See the `FEATURE_*` constants. Bits are only ever added; a bit's meaning
+/// never changes within a major ABI version. May be called before `rrpc_init`.
+#[no_mangle]
+pub extern "C" fn rrpc_features() -> u64 {
+    FEATURES
+}
 
 /// Initialize the rRPC runtime
 ///
-/// Must be called once before any `rrpc_call` invocations.
+/// Must be called before any `rrpc_call` invocations, and again after
+/// `rrpc_shutdown` to start over. A new runtime starts with the methods
+/// declared with `#[rrpc_core::method]`; runtimes from `rrpc_runtime_new()`
+/// start empty.
 ///
 /// # Safety
-/// Safe to call multiple times (idempotent).
+/// Safe to call multiple times (idempotent while initialized).
 #[no_mangle]
 pub unsafe extern "C" fn rrpc_init() -> c_int {
-    GLOBAL_REGISTRY.get_or_init(|| Mutex::new(Registry::new()));
+    DEFAULT_RUNTIME.write().get_or_insert_with(|| {
+        let runtime = Runtime::new();
+        #[cfg(feature = "macros")]
+        runtime.registry_mut().register_collected();
+        Arc::new(runtime)
+    });
+    ERR_SUCCESS
+}
+
+/// Shut down the default runtime
+///
+/// See `rrpc_shutdown_rt`. Afterwards the handle-less functions fail with
+/// `ERR_NOT_INITIALIZED` until `rrpc_init` is called again.
+///
+/// # Safety
+/// Same requirements as `rrpc_shutdown_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_shutdown(outstanding: *mut usize) -> c_int {
+    let Some(rt) = DEFAULT_RUNTIME.write().take() else {
+        return fail(ERR_NOT_INITIALIZED, RpcError::Internal("runtime not initialized".into()));
+    };
+    rrpc_shutdown_rt(Arc::as_ptr(&rt), outstanding)
+}
+
+/// Shut down a runtime
+///
+/// New calls are rejected, in-flight and queued async calls are waited
+/// for, and the handlers are dropped. If `outstanding` is non-null it
+/// receives the number of output buffers (from any runtime) not yet
+/// released with `rrpc_free()`; they stay valid and must still be freed.
+/// A handle from `rrpc_runtime_new()` must still be released with
+/// `rrpc_runtime_free()`.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle; `outstanding` must be null
+/// or valid for writes. Must not be called from a handler or callback.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_shutdown_rt(rt: *const Runtime, outstanding: *mut usize) -> c_int {
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    rt.shutdown();
+
+    if let Some(outstanding) = outstanding.as_mut() {
+        *outstanding = LIVE_BUFFERS.lock().len();
+    }
     ERR_SUCCESS
 }
 
+/// Create a new independent runtime and return an opaque handle to it
+///
+/// The handle must be released with `rrpc_runtime_free()`.
+#[no_mangle]
+pub extern "C" fn rrpc_runtime_new() -> *mut Runtime {
+    Box::into_raw(Box::new(Runtime::new()))
+}
+
+/// Destroy a runtime created by `rrpc_runtime_new()`, dropping its handlers
+///
+/// # Safety
+/// `rt` must come from `rrpc_runtime_new()`, must not be freed twice and
+/// must not be in use by a concurrent call. Null is a no-op.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_runtime_free(rt: *mut Runtime) {
+    if !rt.is_null() {
+        drop(Box::from_raw(rt));
+    }
+}
+
 /// Call an RPC method by name
 ///
 /// # Arguments
@@ -63,7 +405,7 @@ pub unsafe extern "C" fn rrpc_init() -> c_int {
 ///
 /// # Returns
 /// * `ERR_SUCCESS` (0) on success
-/// * Error code (>0) on failure
+/// * Error code (>0) on failure; the message is available via `rrpc_last_error()`
 ///
 /// # Safety
 /// Caller must:
@@ -78,134 +420,2409 @@ pub unsafe extern "C" fn rrpc_call(
     out_ptr: *mut *mut u8,
     out_len: *mut usize,
 ) -> c_int {
-    // Basic validation
-    const MAX_INPUT_LEN: usize = 10 * 1024 * 1024; // 10 MB
-
-    if method_ptr.is_null() {
-        return ERR_PARSE_ERROR;
-    }
+    let rt = default_runtime();
+    rrpc_call_rt(default_handle(&rt), method_ptr, in_ptr, in_len, out_ptr, out_len)
+}
 
-    if in_len > 0 && in_ptr.is_null() {
-        return ERR_PARSE_ERROR;
-    }
+/// Call an RPC method on a specific runtime
+///
+/// Same contract as `rrpc_call`, with `rt` a handle from `rrpc_runtime_new()`.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle; see `rrpc_call` for the
+/// remaining requirements.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_rt(
+    rt: *const Runtime,
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    call_on(rt, method_ptr, in_ptr, in_len, &CallOptions::default(), out_ptr, out_len)
+}
 
-    if in_len > MAX_INPUT_LEN {
-        return ERR_TOO_LARGE;
-    }
+/// Shared implementation of the blocking `rrpc_call*` variants
+unsafe fn call_on(
+    rt: *const Runtime,
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    options: &CallOptions,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    let (method, input) = match call_args(method_ptr, in_ptr, in_len) {
+        Ok(args) => args,
+        Err(rc) => return rc,
+    };
 
     if out_ptr.is_null() || out_len.is_null() {
-        return ERR_INTERNAL;
+        return fail(ERR_INTERNAL, RpcError::Internal("output pointer is null".into()));
     }
-    // Validate registry initialized
-    let Some(registry) = GLOBAL_REGISTRY.get() else {
-        return ERR_NOT_INITIALIZED;
+
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
     };
 
-    // Parse method name
-    let method = match CStr::from_ptr(method_ptr).to_str() {
-        Ok(s) => s,
-        Err(_) => return ERR_PARSE_ERROR,
+    // Call handler on the current snapshot (panics are caught by the registry)
+    let span = CallSpan::new(method, input.len());
+    let (rc, len) = match span.in_scope(|| rt.call_with(method, input, options)) {
+        Ok(data) => {
+            let len = data.len();
+            (write_output(data, out_ptr, out_len), len)
+        }
+        Err(e) => (fail(error_code(&e), e), 0),
     };
+    span.finish(rc, len);
+    rc
+}
 
-    // Get input slice
-    let input = std::slice::from_raw_parts(in_ptr, in_len);
+/// Call an RPC method on the default runtime, writing into a caller buffer
+///
+/// See `rrpc_call_into_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_call_into_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_into(
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    buf_ptr: *mut u8,
+    buf_cap: usize,
+    out_len: *mut usize,
+) -> c_int {
+    let rt = default_runtime();
+    rrpc_call_into_rt(default_handle(&rt), method_ptr, in_ptr, in_len, buf_ptr, buf_cap, out_len)
+}
 
-    // Call handler
-    let registry = registry.lock();
-    let result = match registry.call(method, input) {
-        Ok(data) => data,
-        Err(RpcError::UnknownMethod(_)) => return ERR_UNKNOWN_METHOD,
-        Err(RpcError::NotFound(_)) => return ERR_NOT_FOUND,
-        Err(RpcError::ParseError(_)) => return ERR_PARSE_ERROR,
-        Err(RpcError::SerializationError(_)) => return ERR_SERIALIZATION,
-        Err(RpcError::Internal(_)) => return ERR_INTERNAL,
+/// Call an RPC method on a specific runtime, writing into a caller buffer
+///
+/// The output is copied into `buf_ptr` and its length stored in
+/// `*out_len`; nothing is allocated for the caller and `rrpc_free()` is
+/// not needed. If the output exceeds `buf_cap`, the call returns
+/// `ERR_BUFFER_TOO_SMALL`, leaves the buffer untouched and stores the
+/// required size in `*out_len`. The handler's result is not kept: retrying
+/// with a larger buffer runs the handler again.
+///
+/// # Safety
+/// `buf_ptr` must be valid for `buf_cap` bytes of writes (it may be null
+/// when `buf_cap` is 0) and `out_len` must be valid for writes; see
+/// `rrpc_call_rt` for the remaining requirements.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_into_rt(
+    rt: *const Runtime,
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    buf_ptr: *mut u8,
+    buf_cap: usize,
+    out_len: *mut usize,
+) -> c_int {
+    let (method, input) = match call_args(method_ptr, in_ptr, in_len) {
+        Ok(args) => args,
+        Err(rc) => return rc,
     };
 
-    // Allocate output buffer
-    let len = result.len();
-    let ptr = libc::malloc(len) as *mut u8;
-    if ptr.is_null() {
-        return ERR_INTERNAL;
+    if out_len.is_null() || (buf_cap > 0 && buf_ptr.is_null()) {
+        return fail(ERR_INTERNAL, RpcError::Internal("output pointer is null".into()));
     }
 
-    std::ptr::copy_nonoverlapping(result.as_ptr(), ptr, len);
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
 
-    *out_ptr = ptr;
-    *out_len = len;
+    let span = CallSpan::new(method, input.len());
+    let result = match span.in_scope(|| rt.call(method, input)) {
+        Ok(data) => data,
+        Err(e) => {
+            span.finish(error_code(&e), 0);
+            return fail(error_code(&e), e);
+        }
+    };
+
+    *out_len = result.len();
+    if result.len() > buf_cap {
+        span.finish(ERR_BUFFER_TOO_SMALL, result.len());
+        return fail(
+            ERR_BUFFER_TOO_SMALL,
+            RpcError::Internal(format!("output needs {} bytes, buffer holds {}", result.len(), buf_cap)),
+        );
+    }
 
+    if !result.is_empty() {
+        std::ptr::copy_nonoverlapping(result.as_ptr(), buf_ptr, result.len());
+    }
+    span.finish(ERR_SUCCESS, result.len());
     ERR_SUCCESS
 }
 
-/// Free memory allocated by `rrpc_call`
+/// Create a cancellation token
+///
+/// Pass it to `rrpc_call_with_token` and trigger it from any thread with
+/// `rrpc_cancel()`. Release it with `rrpc_cancel_token_free()`.
+#[no_mangle]
+pub extern "C" fn rrpc_cancel_token_new() -> *mut CancelToken {
+    Box::into_raw(Box::new(CancelToken::new()))
+}
+
+/// Request cancellation of every call using `token`
 ///
 /// # Safety
-/// Must only be called once per buffer returned by `rrpc_call`.
-/// Pointer must not be used after calling this function.
+/// `token` must be null (no-op) or a live token from `rrpc_cancel_token_new()`.
 #[no_mangle]
-pub unsafe extern "C" fn rrpc_free(ptr: *mut u8, _len: usize) {
-    if !ptr.is_null() {
-        libc::free(ptr as *mut libc::c_void);
+pub unsafe extern "C" fn rrpc_cancel(token: *const CancelToken) {
+    if let Some(token) = token.as_ref() {
+        token.cancel();
     }
 }
 
-/// Get the global registry (for testing/advanced usage)
-pub fn get_registry() -> Option<&'static Mutex<Registry>> {
-    GLOBAL_REGISTRY.get()
+/// Release a cancellation token
+///
+/// Calls still using the token keep their own reference, so freeing it
+/// while they run is safe.
+///
+/// # Safety
+/// `token` must come from `rrpc_cancel_token_new()` and must not be freed
+/// twice. Null is a no-op.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_cancel_token_free(token: *mut CancelToken) {
+    if !token.is_null() {
+        drop(Box::from_raw(token));
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_init() {
-        unsafe {
-            let result = rrpc_init();
-            assert_eq!(result, ERR_SUCCESS);
-        }
-    }
+/// Call an RPC method on the default runtime with a cancellation token
+///
+/// See `rrpc_call_with_token_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_call_with_token_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_with_token(
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    token: *const CancelToken,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    let rt = default_runtime();
+    rrpc_call_with_token_rt(default_handle(&rt), method_ptr, in_ptr, in_len, token, out_ptr, out_len)
+}
 
-    #[test]
-    fn test_registry() {
-        unsafe { rrpc_init() };
-        
-        let registry = get_registry().unwrap();
-        let mut reg = registry.lock();
-        
-        reg.register("test", |input| Ok(input.to_vec()));
-        
-        let result = reg.call("test", b"hello").unwrap();
-        assert_eq!(result, b"hello");
-    }
+/// Call an RPC method on a specific runtime with a cancellation token
+///
+/// Same contract as `rrpc_call_rt`. The handler can poll the token through
+/// its `CallContext`; once the token is cancelled the call returns
+/// `ERR_CANCELLED`. A null token behaves like `rrpc_call_rt`.
+///
+/// # Safety
+/// `token` must be null or a live token from `rrpc_cancel_token_new()`;
+/// see `rrpc_call_rt` for the remaining requirements.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_with_token_rt(
+    rt: *const Runtime,
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    token: *const CancelToken,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    let options = CallOptions {
+        cancel: token.as_ref().cloned(),
+        ..CallOptions::default()
+    };
+    call_on(rt, method_ptr, in_ptr, in_len, &options, out_ptr, out_len)
+}
 
-    #[test]
-    fn rrpc_call_null_method_returns_parse_error() {
-        unsafe { rrpc_init(); }
+/// Call an RPC method on the default runtime with a timeout
+///
+/// See `rrpc_call_with_timeout_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_call_with_timeout_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_with_timeout(
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    timeout_ms: u64,
+    token: *const CancelToken,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    let rt = default_runtime();
+    rrpc_call_with_timeout_rt(default_handle(&rt), method_ptr, in_ptr, in_len, timeout_ms, token, out_ptr, out_len)
+}
 
-        let mut out_ptr: *mut u8 = std::ptr::null_mut();
-        let mut out_len: usize = 0;
-        let rc = unsafe { rrpc_call(std::ptr::null::<c_char>(), b"".as_ptr(), 0, &mut out_ptr, &mut out_len) };
-        assert_eq!(rc, ERR_PARSE_ERROR);
+/// Call an RPC method on a specific runtime with a timeout
+///
+/// Same contract as `rrpc_call_with_token_rt`. If the handler has not
+/// returned `timeout_ms` milliseconds after the call started, the call
+/// returns `ERR_TIMEOUT` without waiting for it; the handler sees the
+/// deadline through its `CallContext`. A `timeout_ms` of 0 sets no per-call
+/// deadline (the method's default timeout, if any, still applies).
+///
+/// # Safety
+/// See `rrpc_call_with_token_rt`.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn rrpc_call_with_timeout_rt(
+    rt: *const Runtime,
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    timeout_ms: u64,
+    token: *const CancelToken,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    let mut options = CallOptions {
+        cancel: token.as_ref().cloned(),
+        ..CallOptions::default()
+    };
+    if timeout_ms > 0 {
+        options = options.with_timeout(Duration::from_millis(timeout_ms));
     }
+    call_on(rt, method_ptr, in_ptr, in_len, &options, out_ptr, out_len)
+}
 
-    #[test]
-    fn rrpc_call_oversized_input_returns_too_large() {
-        unsafe { rrpc_init(); }
-        // construct a dummy buffer larger than MAX_INPUT_LEN (10MB)
-        let large_size = 10 * 1024 * 1024 + 1;
-        let vec = vec![0u8; large_size];
-        let mut out_ptr: *mut u8 = std::ptr::null_mut();
-        let mut out_len: usize = 0;
-        let rc = unsafe { rrpc_call("test\0".as_ptr() as *const c_char, vec.as_ptr(), vec.len(), &mut out_ptr, &mut out_len) };
-        assert_eq!(rc, ERR_TOO_LARGE);
-    }
+/// Create a capability token that grants nothing
+///
+/// Add capabilities with `rrpc_grant()` and pass the token to
+/// `rrpc_call_with_capabilities`. Release it with
+/// `rrpc_capability_token_free()`.
+#[no_mangle]
+pub extern "C" fn rrpc_capability_token_new() -> *mut CapabilityToken {
+    Box::into_raw(Box::new(CapabilityToken::new()))
+}
 
-    #[test]
-    fn rrpc_call_unknown_method_returns_unknown() {
+/// Grant a capability to `token`
+///
+/// `capability_ptr` names one capability, such as `"process.terminate"`,
+/// or a pattern: `"*"` grants everything and `"process.*"` every
+/// capability starting with `process.`. Grants apply to calls already
+/// holding the token. Returns `ERR_PARSE_ERROR` for a null token or an
+/// invalid capability.
+///
+/// # Safety
+/// `token` must be null or a live token from `rrpc_capability_token_new()`
+/// and `capability_ptr` null or valid null-terminated UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_grant(token: *const CapabilityToken, capability_ptr: *const c_char) -> c_int {
+    let capability = match str_arg(capability_ptr, "capability") {
+        Ok(capability) => capability,
+        Err(rc) => return rc,
+    };
+    let Some(token) = token.as_ref() else {
+        return fail(ERR_PARSE_ERROR, RpcError::ParseError("capability token is null".into()));
+    };
+
+    token.grant(capability);
+    ERR_SUCCESS
+}
+
+/// Create a capability token that grants nothing, naming its holder
+///
+/// Like `rrpc_capability_token_new()`, but calls made with the token are
+/// attributed to `identity_ptr` in the audit log. Returns null, recording
+/// `ERR_PARSE_ERROR`, if the identity is null or not UTF-8.
+///
+/// # Safety
+/// `identity_ptr` must be null or valid null-terminated UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_capability_token_new_named(identity_ptr: *const c_char) -> *mut CapabilityToken {
+    match str_arg(identity_ptr, "identity") {
+        Ok(identity) => Box::into_raw(Box::new(CapabilityToken::named(identity))),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Release a capability token
+///
+/// Calls still using the token keep their own reference, so freeing it
+/// while they run is safe.
+///
+/// # Safety
+/// `token` must come from `rrpc_capability_token_new()` and must not be
+/// freed twice. Null is a no-op.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_capability_token_free(token: *mut CapabilityToken) {
+    if !token.is_null() {
+        drop(Box::from_raw(token));
+    }
+}
+
+/// Call an RPC method on the default runtime with a capability token
+///
+/// See `rrpc_call_with_capabilities_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_call_with_capabilities_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_with_capabilities(
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    token: *const CapabilityToken,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    let rt = default_runtime();
+    rrpc_call_with_capabilities_rt(default_handle(&rt), method_ptr, in_ptr, in_len, token, out_ptr, out_len)
+}
+
+/// Call an RPC method on a specific runtime on behalf of a less trusted caller
+///
+/// Same contract as `rrpc_call_rt`, but the call and every nested call it
+/// makes only have the capabilities granted to `token`. If the method
+/// requires one the token lacks, the call returns `ERR_PERMISSION_DENIED`
+/// without running it. A null token uses the runtime's default grants,
+/// like `rrpc_call_rt`.
+///
+/// # Safety
+/// `token` must be null or a live token from `rrpc_capability_token_new()`;
+/// see `rrpc_call_rt` for the remaining requirements.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_with_capabilities_rt(
+    rt: *const Runtime,
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    token: *const CapabilityToken,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    let options = CallOptions {
+        capabilities: token.as_ref().cloned(),
+        ..CallOptions::default()
+    };
+    call_on(rt, method_ptr, in_ptr, in_len, &options, out_ptr, out_len)
+}
+
+/// Require a capability for methods of the default runtime
+///
+/// See `rrpc_require_capability_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_require_capability_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_require_capability(pattern_ptr: *const c_char, capability_ptr: *const c_char) -> c_int {
+    let rt = default_runtime();
+    rrpc_require_capability_rt(default_handle(&rt), pattern_ptr, capability_ptr)
+}
+
+/// Require a capability for every method of a runtime matching a pattern
+///
+/// `pattern_ptr` is `"*"`, a prefix ending in `*` such as `"sys.*"` (for
+/// example the namespace of a plugin), or an exact method name; it also
+/// covers methods registered later. Calls that lack the capability fail
+/// with `ERR_PERMISSION_DENIED`.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle; `pattern_ptr` and
+/// `capability_ptr` must be null or valid null-terminated UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_require_capability_rt(
+    rt: *const Runtime,
+    pattern_ptr: *const c_char,
+    capability_ptr: *const c_char,
+) -> c_int {
+    let pattern = match str_arg(pattern_ptr, "pattern") {
+        Ok(pattern) => pattern,
+        Err(rc) => return rc,
+    };
+    let capability = match str_arg(capability_ptr, "capability") {
+        Ok(capability) => capability,
+        Err(rc) => return rc,
+    };
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    match rt.try_registry_mut() {
+        Ok(mut registry) => registry.require(pattern, capability),
+        Err(e) => return fail(ERR_INTERNAL, e),
+    }
+    ERR_SUCCESS
+}
+
+/// Grant a capability to calls on the default runtime made without a token
+///
+/// See `rrpc_grant_default_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_grant_default_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_grant_default(capability_ptr: *const c_char) -> c_int {
+    let rt = default_runtime();
+    rrpc_grant_default_rt(default_handle(&rt), capability_ptr)
+}
+
+/// Grant a capability to every call on a runtime made without a token
+///
+/// This is the runtime's policy for trusted callers: `rrpc_call` and the
+/// other calls without a capability token, including those of Rust code,
+/// have exactly the default grants, which are initially empty. Accepts the
+/// same patterns as `rrpc_grant`.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle and `capability_ptr` null or
+/// valid null-terminated UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_grant_default_rt(rt: *const Runtime, capability_ptr: *const c_char) -> c_int {
+    let capability = match str_arg(capability_ptr, "capability") {
+        Ok(capability) => capability,
+        Err(rc) => return rc,
+    };
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    rt.registry().grant_default(capability);
+    ERR_SUCCESS
+}
+
+/// Write the audit log of the default runtime to a file
+///
+/// See `rrpc_audit_file_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_audit_file_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_audit_file(path_ptr: *const c_char, key_ptr: *const u8, key_len: usize) -> c_int {
+    let rt = default_runtime();
+    rrpc_audit_file_rt(default_handle(&rt), path_ptr, key_ptr, key_len)
+}
+
+/// Write the audit log of a runtime to a MAC-chained file
+///
+/// Calls of audited methods are appended to `path_ptr`, continuing the
+/// chain of an existing log. Records are chained with HMAC-SHA256 under the
+/// `key_len` bytes at `key_ptr`, which are kept in memory but never written
+/// to the log; verifying needs the same key. Fails with `ERR_PARSE_ERROR`
+/// for an empty key, and with `ERR_INTERNAL` if the file cannot be opened
+/// or does not pass `rrpc_audit_verify` with the key. A null path stops
+/// auditing and ignores the key. See the `audit` module for the record
+/// format and what the chain does and does not detect.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle, `path_ptr` null or valid
+/// null-terminated UTF-8, and `key_ptr` valid for `key_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_audit_file_rt(
+    rt: *const Runtime,
+    path_ptr: *const c_char,
+    key_ptr: *const u8,
+    key_len: usize,
+) -> c_int {
+    let target = if path_ptr.is_null() {
+        None
+    } else {
+        match str_arg(path_ptr, "path").and_then(|path| Ok((path, key_arg(key_ptr, key_len)?))) {
+            Ok(target) => Some(target),
+            Err(rc) => return rc,
+        }
+    };
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    let sink = match target.map(|(path, key)| audit::FileSink::open(path, key)).transpose() {
+        Ok(sink) => sink.map(|sink| Arc::new(sink) as Arc<dyn audit::AuditSink>),
+        Err(e) => return fail(ERR_INTERNAL, e),
+    };
+    match rt.try_registry_mut() {
+        Ok(mut registry) => registry.set_audit_sink(sink),
+        Err(e) => return fail(ERR_INTERNAL, e),
+    }
+    ERR_SUCCESS
+}
+
+/// Audit methods of the default runtime
+///
+/// See `rrpc_audit_methods_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_audit_methods_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_audit_methods(pattern_ptr: *const c_char) -> c_int {
+    let rt = default_runtime();
+    rrpc_audit_methods_rt(default_handle(&rt), pattern_ptr)
+}
+
+/// Audit every method of a runtime matching a pattern
+///
+/// Accepts the same patterns as `rrpc_require_capability_rt`, in addition
+/// to methods flagged as audited when registered.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle and `pattern_ptr` null or
+/// valid null-terminated UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_audit_methods_rt(rt: *const Runtime, pattern_ptr: *const c_char) -> c_int {
+    let pattern = match str_arg(pattern_ptr, "pattern") {
+        Ok(pattern) => pattern,
+        Err(rc) => return rc,
+    };
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    match rt.try_registry_mut() {
+        Ok(mut registry) => registry.audit(pattern),
+        Err(e) => return fail(ERR_INTERNAL, e),
+    }
+    ERR_SUCCESS
+}
+
+/// Check the MAC chain of an audit log file
+///
+/// Uses the key the log was written with (see `rrpc_audit_file_rt`).
+/// Writes the number of records that passed to `*out_records` and the
+/// line number (from 1) of the first tampered or incomplete record, or of
+/// the first record when the key is wrong, to `*out_broken_line`, or 0 if
+/// the whole log is intact. Records dropped from the end are not detected;
+/// compare `*out_records` with a count kept elsewhere for that. Returns
+/// `ERR_PARSE_ERROR` for an empty key and `ERR_INTERNAL` if the file cannot
+/// be read; a missing file is an empty, intact log. Needs no runtime.
+///
+/// # Safety
+/// `path_ptr` must be null or valid null-terminated UTF-8, `key_ptr` valid
+/// for `key_len` bytes, and `out_records` and `out_broken_line` valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_audit_verify(
+    path_ptr: *const c_char,
+    key_ptr: *const u8,
+    key_len: usize,
+    out_records: *mut u64,
+    out_broken_line: *mut u64,
+) -> c_int {
+    if out_records.is_null() || out_broken_line.is_null() {
+        return fail(ERR_INTERNAL, RpcError::Internal("output pointer is null".into()));
+    }
+    let path = match str_arg(path_ptr, "path") {
+        Ok(path) => path,
+        Err(rc) => return rc,
+    };
+    let key = match key_arg(key_ptr, key_len) {
+        Ok(key) => key,
+        Err(rc) => return rc,
+    };
+
+    match audit::verify(path, key) {
+        Ok(verification) => {
+            *out_records = verification.records;
+            *out_broken_line = verification.broken.map_or(0, |broken| broken.line);
+            ERR_SUCCESS
+        }
+        Err(e) => fail(ERR_INTERNAL, e),
+    }
+}
+
+/// Completion callback for `rrpc_call_async`
+///
+/// Invoked once on a worker thread with the call id, the result code and
+/// the output buffer (null/0 on failure). The receiver owns the buffer and
+/// must release it with `rrpc_free()`. On failure the error message can be
+/// read with `rrpc_last_error()` from inside the callback.
+pub type CompletionCallback =
+    extern "C" fn(call_id: u64, rc: c_int, out_ptr: *mut u8, out_len: usize, user_data: *mut c_void);
+
+/// Host pointer carried to a worker thread; the host guarantees its validity
+struct UserData(*mut c_void);
+
+// SAFETY: the pointer is only handed back to the host's callback, which is
+// responsible for any synchronisation it needs
+unsafe impl Send for UserData {}
+
+// SAFETY: as above; host methods may be called from several threads at once
+unsafe impl Sync for UserData {}
+
+/// Call an RPC method asynchronously on the default runtime
+///
+/// See `rrpc_call_async_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_call_async_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_async(
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    callback: Option<CompletionCallback>,
+    user_data: *mut c_void,
+) -> u64 {
+    let rt = default_runtime();
+    rrpc_call_async_rt(default_handle(&rt), method_ptr, in_ptr, in_len, callback, user_data)
+}
+
+/// Call an RPC method asynchronously on a specific runtime
+///
+/// The input is copied, so the caller may release it as soon as this
+/// returns. The handler runs on the runtime's worker pool and `callback`
+/// is invoked with the result.
+///
+/// # Returns
+/// * A non-zero call id once the call is queued
+/// * `0` if the call could not be queued; the reason is available via
+///   `rrpc_last_error()` and `callback` is never invoked
+///
+/// On a runtime that has been shut down, `callback` is invoked before this
+/// returns, on the calling thread, with `ERR_INTERNAL`.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle that outlives the call;
+/// `method_ptr` must be valid null-terminated UTF-8 and `in_ptr` must point
+/// to at least `in_len` bytes. `user_data` is passed through untouched.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_async_rt(
+    rt: *const Runtime,
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    callback: Option<CompletionCallback>,
+    user_data: *mut c_void,
+) -> u64 {
+    let (method, input) = match call_args(method_ptr, in_ptr, in_len) {
+        Ok(args) => args,
+        Err(_) => return 0,
+    };
+
+    let Some(callback) = callback else {
+        fail(ERR_INTERNAL, RpcError::Internal("completion callback is null".into()));
+        return 0;
+    };
+
+    let Ok(rt) = runtime_ref(rt) else {
+        return 0;
+    };
+
+    let user_data = UserData(user_data);
+    rt.call_async(method, input.to_vec(), move |id, result| {
+        let user_data = user_data;
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = match result {
+            Ok(data) => write_output(data, &mut out_ptr, &mut out_len),
+            Err(e) => fail(error_code(&e), e),
+        };
+        callback(id, rc, out_ptr, out_len, user_data.0);
+    })
+}
+
+/// Per-frame callback for `rrpc_call_stream`
+///
+/// The frame is only valid for the duration of the callback. Return
+/// `0` to continue or any other value to stop the stream.
+pub type StreamItemCallback = extern "C" fn(item_ptr: *const u8, item_len: usize, user_data: *mut c_void) -> c_int;
+
+/// End-of-stream callback for `rrpc_call_stream`
+///
+/// Invoked exactly once after the last frame with `ERR_SUCCESS`, or with
+/// the error code that terminated the stream.
+pub type StreamDoneCallback = extern "C" fn(rc: c_int, user_data: *mut c_void);
+
+/// Call a streaming method on the default runtime
+///
+/// See `rrpc_call_stream_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_call_stream_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_stream(
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    on_item: Option<StreamItemCallback>,
+    on_done: Option<StreamDoneCallback>,
+    user_data: *mut c_void,
+) -> c_int {
+    let rt = default_runtime();
+    rrpc_call_stream_rt(default_handle(&rt), method_ptr, in_ptr, in_len, on_item, on_done, user_data)
+}
+
+/// Call a streaming method on a specific runtime
+///
+/// Blocks until the stream ends, forwarding each frame to `on_item` on the
+/// calling thread. `on_done` then receives the final code, which is also
+/// returned. Stopping the stream from `on_item` is not an error. Unary
+/// methods produce a single frame.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle; `method_ptr` must be valid
+/// null-terminated UTF-8 and `in_ptr` must point to at least `in_len`
+/// bytes. `user_data` is passed through untouched.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_stream_rt(
+    rt: *const Runtime,
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    on_item: Option<StreamItemCallback>,
+    on_done: Option<StreamDoneCallback>,
+    user_data: *mut c_void,
+) -> c_int {
+    let rc = stream_on(rt, method_ptr, in_ptr, in_len, on_item, user_data);
+    if let Some(on_done) = on_done {
+        on_done(rc, user_data);
+    }
+    rc
+}
+
+unsafe fn stream_on(
+    rt: *const Runtime,
+    method_ptr: *const c_char,
+    in_ptr: *const u8,
+    in_len: usize,
+    on_item: Option<StreamItemCallback>,
+    user_data: *mut c_void,
+) -> c_int {
+    let (method, input) = match call_args(method_ptr, in_ptr, in_len) {
+        Ok(args) => args,
+        Err(rc) => return rc,
+    };
+
+    let Some(on_item) = on_item else {
+        return fail(ERR_INTERNAL, RpcError::Internal("item callback is null".into()));
+    };
+
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    match rt.call_stream(method, input, |frame| on_item(frame.as_ptr(), frame.len(), user_data) == 0) {
+        Ok(()) => ERR_SUCCESS,
+        Err(e) => fail(error_code(&e), e),
+    }
+}
+
+/// Host-implemented method registered with `rrpc_register_host`
+///
+/// Receives the input and writes a host-allocated output buffer to
+/// `*out_ptr`/`*out_len` (null/0 for no output). Returns `ERR_SUCCESS` or
+/// an error code; on failure the output, if any, is a UTF-8 error message.
+/// May be called from any thread, concurrently.
+pub type HostMethod = extern "C" fn(
+    in_ptr: *const u8,
+    in_len: usize,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+    user_data: *mut c_void,
+) -> c_int;
+
+/// Releases an output buffer produced by a `HostMethod`
+pub type HostFreeFn = extern "C" fn(ptr: *mut u8, len: usize, user_data: *mut c_void);
+
+/// Map a code returned by a host method back to an `RpcError`
+fn host_error(method: &str, rc: c_int, message: String) -> RpcError {
+    match rc {
+        ERR_UNKNOWN_METHOD => RpcError::UnknownMethod(message),
+        ERR_NOT_FOUND => RpcError::NotFound(message),
+        ERR_PARSE_ERROR => RpcError::ParseError(message),
+        ERR_SERIALIZATION => RpcError::SerializationError(message),
+        ERR_PANIC => RpcError::Panic(message),
+        ERR_CANCELLED => RpcError::Cancelled,
+        ERR_TIMEOUT => RpcError::Timeout,
+        ERR_PERMISSION_DENIED => RpcError::PermissionDenied(message),
+        _ if message.is_empty() => RpcError::Internal(format!("host method '{}' failed with code {}", method, rc)),
+        _ => RpcError::Internal(message),
+    }
+}
+
+/// Register a host-implemented method on the default runtime
+///
+/// See `rrpc_register_host_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_register_host_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_register_host(
+    method_ptr: *const c_char,
+    func: Option<HostMethod>,
+    user_data: *mut c_void,
+    free_fn: Option<HostFreeFn>,
+) -> c_int {
+    let rt = default_runtime();
+    rrpc_register_host_rt(default_handle(&rt), method_ptr, func, user_data, free_fn)
+}
+
+/// Register a host-implemented method on a specific runtime
+///
+/// The method becomes an ordinary handler: Rust code, other handlers (via
+/// `CallContext::call`) and the host itself can call it, with the usual
+/// dispatch, timeouts and error mapping. Its output is copied into a Rust
+/// buffer and then handed to `free_fn` (skipped when `free_fn` is null,
+/// for hosts returning static data). Registering an existing name replaces
+/// the method. Fails with `ERR_INTERNAL` once the runtime is shut down.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle and `method_ptr` valid
+/// null-terminated UTF-8. `func`, `free_fn` and `user_data` must stay
+/// valid until the method is replaced or the runtime is shut down.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_register_host_rt(
+    rt: *const Runtime,
+    method_ptr: *const c_char,
+    func: Option<HostMethod>,
+    user_data: *mut c_void,
+    free_fn: Option<HostFreeFn>,
+) -> c_int {
+    let (method, _) = match call_args(method_ptr, std::ptr::null(), 0) {
+        Ok(args) => args,
+        Err(rc) => return rc,
+    };
+
+    let Some(func) = func else {
+        return fail(ERR_INTERNAL, RpcError::Internal("host function is null".into()));
+    };
+
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    let mut registry = match rt.try_registry_mut() {
+        Ok(registry) => registry,
+        Err(e) => return fail(ERR_INTERNAL, e),
+    };
+    let name = method.to_string();
+    let user_data = UserData(user_data);
+    registry.register(method, move |input| {
+        let user_data = &user_data;
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = func(input.as_ptr(), input.len(), &mut out_ptr, &mut out_len, user_data.0);
+
+        let output = if out_ptr.is_null() || out_len == 0 {
+            Vec::new()
+        } else {
+            // SAFETY: the host promises `out_len` readable bytes at `out_ptr`
+            let output = unsafe { std::slice::from_raw_parts(out_ptr, out_len) }.to_vec();
+            if let Some(free_fn) = free_fn {
+                free_fn(out_ptr, out_len, user_data.0);
+            }
+            output
+        };
+
+        match rc {
+            ERR_SUCCESS => Ok(output),
+            rc => Err(host_error(&name, rc, String::from_utf8_lossy(&output).into_owned())),
+        }
+    });
+
+    ERR_SUCCESS
+}
+
+/// Log callback installed with `rrpc_set_log_callback`
+///
+/// Receives the level (`LOG_ERROR` .. `LOG_TRACE`), the target (usually
+/// the Rust module path) and the message, both UTF-8 and not
+/// null-terminated. The buffers are only valid during the call. May be
+/// called from any thread, concurrently.
+pub type LogCallback = extern "C" fn(
+    level: c_int,
+    target_ptr: *const u8,
+    target_len: usize,
+    msg_ptr: *const u8,
+    msg_len: usize,
+    user_data: *mut c_void,
+);
+
+/// Forward log records from the runtime and handlers to the host
+///
+/// Records are emitted with the `log` crate; those at or above the level
+/// set with `rrpc_set_log_level` (default `LOG_INFO`) are passed to
+/// `callback`. A null `callback` stops forwarding. Installing a callback
+/// fails with `ERR_INTERNAL` if some other `log` logger was installed in
+/// the process first. Applies to the whole process, not to one runtime,
+/// and may be called before `rrpc_init`.
+///
+/// # Safety
+/// `callback` and `user_data` must stay valid until the callback is
+/// replaced or cleared, and calls already in progress on other threads
+/// have returned.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_set_log_callback(callback: Option<LogCallback>, user_data: *mut c_void) -> c_int {
+    let sink = callback.map(|callback| {
+        let user_data = UserData(user_data);
+        let sink: logging::Sink = Arc::new(move |level: log::Level, target: &str, message: &str| {
+            let user_data = &user_data;
+            callback(
+                level as c_int,
+                target.as_ptr(),
+                target.len(),
+                message.as_ptr(),
+                message.len(),
+                user_data.0,
+            );
+        });
+        sink
+    });
+
+    match logging::set_sink(sink) {
+        Ok(()) => ERR_SUCCESS,
+        Err(e) => fail(ERR_INTERNAL, e),
+    }
+}
+
+/// Set the most verbose level forwarded to the log callback
+///
+/// `level` is one of `LOG_OFF` .. `LOG_TRACE`; anything else fails with
+/// `ERR_INTERNAL`. Takes effect immediately on all threads.
+#[no_mangle]
+pub extern "C" fn rrpc_set_log_level(level: c_int) -> c_int {
+    match logging::set_level(usize::try_from(level).unwrap_or(usize::MAX)) {
+        Ok(()) => ERR_SUCCESS,
+        Err(e) => fail(ERR_INTERNAL, e),
+    }
+}
+
+/// Borrow a required null-terminated UTF-8 string argument
+unsafe fn str_arg<'a>(ptr: *const c_char, what: &str) -> Result<&'a str, c_int> {
+    if ptr.is_null() {
+        return Err(fail(ERR_PARSE_ERROR, RpcError::ParseError(format!("{} is null", what))));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|e| fail(ERR_PARSE_ERROR, RpcError::ParseError(format!("{}: {}", what, e))))
+}
+
+/// Borrow a required non-empty key argument
+unsafe fn key_arg<'a>(ptr: *const u8, len: usize) -> Result<&'a [u8], c_int> {
+    if ptr.is_null() || len == 0 {
+        return Err(fail(ERR_PARSE_ERROR, RpcError::ParseError("key is empty".into())));
+    }
+    Ok(std::slice::from_raw_parts(ptr, len))
+}
+
+/// Load a plugin into the default runtime
+///
+/// See `rrpc_load_plugin_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_load_plugin_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_load_plugin(path_ptr: *const c_char, namespace_ptr: *const c_char) -> c_int {
+    let rt = default_runtime();
+    rrpc_load_plugin_rt(default_handle(&rt), path_ptr, namespace_ptr)
+}
+
+/// Load a plugin library into a specific runtime
+///
+/// Opens the cdylib at `path_ptr`, checks its plugin ABI version and
+/// registers its methods as `namespace.method` (unprefixed if the
+/// namespace is empty). Returns `ERR_PLUGIN` if the library cannot be
+/// loaded, is incompatible, or would shadow an existing method; nothing is
+/// registered in that case.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle; both strings must be valid
+/// null-terminated UTF-8. The library's code runs unchecked in this
+/// process, so only load trusted plugins.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_load_plugin_rt(
+    rt: *const Runtime,
+    path_ptr: *const c_char,
+    namespace_ptr: *const c_char,
+) -> c_int {
+    let path = match str_arg(path_ptr, "plugin path") {
+        Ok(path) => path,
+        Err(rc) => return rc,
+    };
+    let namespace = match str_arg(namespace_ptr, "plugin namespace") {
+        Ok(namespace) => namespace,
+        Err(rc) => return rc,
+    };
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    match rt.load_plugin(path, namespace) {
+        Ok(_) => ERR_SUCCESS,
+        Err(e) => fail(ERR_PLUGIN, e),
+    }
+}
+
+/// Unload a plugin from the default runtime
+///
+/// See `rrpc_unload_plugin_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_unload_plugin_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_unload_plugin(namespace_ptr: *const c_char) -> c_int {
+    let rt = default_runtime();
+    rrpc_unload_plugin_rt(default_handle(&rt), namespace_ptr)
+}
+
+/// Remove the methods of the plugin loaded under a namespace
+///
+/// Returns `ERR_PLUGIN` if no plugin is loaded under that namespace. The
+/// library is closed once calls still using its handlers have finished.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle; `namespace_ptr` must be
+/// valid null-terminated UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_unload_plugin_rt(rt: *const Runtime, namespace_ptr: *const c_char) -> c_int {
+    let namespace = match str_arg(namespace_ptr, "plugin namespace") {
+        Ok(namespace) => namespace,
+        Err(rc) => return rc,
+    };
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    match rt.unload_plugin(namespace) {
+        Ok(_) => ERR_SUCCESS,
+        Err(e) => fail(ERR_PLUGIN, e),
+    }
+}
+
+/// List the methods registered on the default runtime
+///
+/// See `rrpc_list_methods_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_list_methods_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_list_methods(out_ptr: *mut *mut u8, out_len: *mut usize) -> c_int {
+    let rt = default_runtime();
+    rrpc_list_methods_rt(default_handle(&rt), out_ptr, out_len)
+}
+
+/// List the methods registered on a specific runtime
+///
+/// Writes a UTF-8 JSON array with one object per method, sorted by name:
+/// `{"name", "kind", "description", "input_type", "output_type",
+/// "deprecated", "timeout_ms", "requires", "audited"}`. `kind` is
+/// `"unary"` or `"stream"`; `requires` lists the capabilities a caller
+/// needs; missing metadata is `null`. Release the buffer with `rrpc_free()`.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle; `out_ptr` and `out_len` must
+/// be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_list_methods_rt(
+    rt: *const Runtime,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    if out_ptr.is_null() || out_len.is_null() {
+        return fail(ERR_INTERNAL, RpcError::Internal("output pointer is null".into()));
+    }
+
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    let json = introspect::to_json(&rt.registry().describe_all());
+    write_output(json.into_bytes(), out_ptr, out_len)
+}
+
+/// Read the call statistics of the default runtime
+///
+/// See `rrpc_stats_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_stats_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_stats(out_ptr: *mut *mut u8, out_len: *mut usize) -> c_int {
+    let rt = default_runtime();
+    rrpc_stats_rt(default_handle(&rt), out_ptr, out_len)
+}
+
+/// Read the call statistics of a specific runtime
+///
+/// Writes a UTF-8 JSON object: `latency_bounds_us`, the upper bounds of
+/// the latency buckets, and `methods`, one object per method called so
+/// far, sorted by name: `{"name", "calls", "errors", "bytes_in",
+/// "bytes_out", "latency_us": {"total", "max", "buckets"}}`. `errors` maps
+/// error kinds to counts; `buckets` has one more entry than the bounds for
+/// slower calls. Release the buffer with `rrpc_free()`.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle; `out_ptr` and `out_len` must
+/// be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_stats_rt(rt: *const Runtime, out_ptr: *mut *mut u8, out_len: *mut usize) -> c_int {
+    if out_ptr.is_null() || out_len.is_null() {
+        return fail(ERR_INTERNAL, RpcError::Internal("output pointer is null".into()));
+    }
+
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    let json = stats::to_json(&rt.registry().stats());
+    write_output(json.into_bytes(), out_ptr, out_len)
+}
+
+/// Check whether the default runtime has a method
+///
+/// See `rrpc_has_method_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_has_method_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_has_method(method_ptr: *const c_char) -> c_int {
+    let rt = default_runtime();
+    rrpc_has_method_rt(default_handle(&rt), method_ptr)
+}
+
+/// Check whether a specific runtime has a method
+///
+/// Returns `1` if the method is registered and `0` otherwise. An invalid
+/// name or runtime handle also yields `0`, with the reason available via
+/// `rrpc_last_error()`.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle; `method_ptr` must be null or
+/// valid null-terminated UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_has_method_rt(rt: *const Runtime, method_ptr: *const c_char) -> c_int {
+    let Ok((method, _)) = call_args(method_ptr, std::ptr::null(), 0) else {
+        return 0;
+    };
+
+    let Ok(rt) = runtime_ref(rt) else {
+        return 0;
+    };
+
+    c_int::from(rt.registry().has_method(method))
+}
+
+/// Run batch entries concurrently (`rrpc_call_batch` flag)
+pub const BATCH_PARALLEL: u32 = 1 << 0;
+/// Skip remaining batch entries after the first failure (`rrpc_call_batch` flag)
+pub const BATCH_STOP_ON_ERROR: u32 = 1 << 1;
+
+/// One call in a `rrpc_call_batch` request
+#[repr(C)]
+pub struct BatchEntry {
+    /// Null-terminated UTF-8 method name
+    pub method: *const c_char,
+    /// Input buffer (may be null when `in_len` is 0)
+    pub in_ptr: *const u8,
+    /// Input length in bytes
+    pub in_len: usize,
+}
+
+/// Outcome of one `rrpc_call_batch` entry
+///
+/// On success `out_ptr`/`out_len` hold an output buffer that must be
+/// released with `rrpc_free()`; otherwise they are null/0.
+#[repr(C)]
+pub struct BatchResult {
+    /// Error code of this entry (`ERR_CANCELLED` if it was skipped)
+    pub rc: c_int,
+    /// Output buffer
+    pub out_ptr: *mut u8,
+    /// Output length in bytes
+    pub out_len: usize,
+}
+
+/// Call several methods on the default runtime in one crossing
+///
+/// See `rrpc_call_batch_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_call_batch_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_batch(
+    entries: *const BatchEntry,
+    count: usize,
+    flags: u32,
+    results: *mut BatchResult,
+) -> c_int {
+    let rt = default_runtime();
+    rrpc_call_batch_rt(default_handle(&rt), entries, count, flags, results)
+}
+
+/// Call several methods on a specific runtime in one crossing
+///
+/// Runs the `count` calls in `entries` on one registry snapshot and writes
+/// one `BatchResult` per entry, in the same order. `flags` combines
+/// `BATCH_PARALLEL` and `BATCH_STOP_ON_ERROR`; entries skipped after a
+/// failure get `ERR_CANCELLED`. Each entry that runs is traced, counted,
+/// audited and intercepted like a separate `rrpc_call_rt`.
+///
+/// # Returns
+/// * `ERR_SUCCESS` (0) if every entry succeeded
+/// * The code of the first failed entry otherwise; its message is available
+///   via `rrpc_last_error()`. Results are written either way.
+///
+/// # Safety
+/// `rt` must be null or a live runtime handle. `entries` must point to
+/// `count` valid entries and `results` must be valid for `count` writes.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_batch_rt(
+    rt: *const Runtime,
+    entries: *const BatchEntry,
+    count: usize,
+    flags: u32,
+    results: *mut BatchResult,
+) -> c_int {
+    batch_on(rt, entries, count, flags, &CallOptions::default(), results)
+}
+
+/// Call several methods on the default runtime with a capability token
+///
+/// See `rrpc_call_batch_with_capabilities_rt`.
+///
+/// # Safety
+/// Same requirements as `rrpc_call_batch_with_capabilities_rt`.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_batch_with_capabilities(
+    entries: *const BatchEntry,
+    count: usize,
+    flags: u32,
+    token: *const CapabilityToken,
+    results: *mut BatchResult,
+) -> c_int {
+    let rt = default_runtime();
+    rrpc_call_batch_with_capabilities_rt(default_handle(&rt), entries, count, flags, token, results)
+}
+
+/// Call several methods on a specific runtime on behalf of a less trusted caller
+///
+/// Same contract as `rrpc_call_batch_rt`, but every entry, and every nested
+/// call it makes, only has the capabilities granted to `token`, as with
+/// `rrpc_call_with_capabilities_rt`. Entries needing a capability the token
+/// lacks report `ERR_PERMISSION_DENIED`. A null token uses the runtime's
+/// default grants, like `rrpc_call_batch_rt`.
+///
+/// # Safety
+/// `token` must be null or a live token from `rrpc_capability_token_new()`;
+/// see `rrpc_call_batch_rt` for the remaining requirements.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_call_batch_with_capabilities_rt(
+    rt: *const Runtime,
+    entries: *const BatchEntry,
+    count: usize,
+    flags: u32,
+    token: *const CapabilityToken,
+    results: *mut BatchResult,
+) -> c_int {
+    let options = CallOptions {
+        capabilities: token.as_ref().cloned(),
+        ..CallOptions::default()
+    };
+    batch_on(rt, entries, count, flags, &options, results)
+}
+
+/// Shared implementation of the `rrpc_call_batch*` variants
+unsafe fn batch_on(
+    rt: *const Runtime,
+    entries: *const BatchEntry,
+    count: usize,
+    flags: u32,
+    options: &CallOptions,
+    results: *mut BatchResult,
+) -> c_int {
+    if count == 0 {
+        return ERR_SUCCESS;
+    }
+
+    if entries.is_null() || results.is_null() {
+        return fail(ERR_INTERNAL, RpcError::Internal("batch entries or results pointer is null".into()));
+    }
+
+    let rt = match runtime_ref(rt) {
+        Ok(rt) => rt,
+        Err(rc) => return rc,
+    };
+
+    let entries = std::slice::from_raw_parts(entries, count);
+    let results = std::slice::from_raw_parts_mut(results, count);
+
+    // Validate up front so workers only see borrowed Rust data
+    let args: Vec<_> = entries
+        .iter()
+        .map(|entry| parse_args(entry.method, entry.in_ptr, entry.in_len))
+        .collect();
+
+    let mode = BatchMode {
+        parallel: flags & BATCH_PARALLEL != 0,
+        stop_on_error: flags & BATCH_STOP_ON_ERROR != 0,
+    };
+    let outcomes = rt
+        .run_batch(count, mode, |registry, i| match &args[i] {
+            Ok((method, input)) => {
+                // Same span as `call_on`; stats, audit and interceptors run in `Registry::dispatch`
+                let span = CallSpan::new(method, input.len());
+                let result = span.in_scope(|| rt.run_on(registry, method, input, options));
+                match &result {
+                    Ok(data) => span.finish(ERR_SUCCESS, data.len()),
+                    Err(e) => span.finish(error_code(e), 0),
+                }
+                result.map_err(|e| (error_code(&e), e))
+            }
+            Err((code, err)) => Err((*code, err.clone())),
+        })
+        .unwrap_or_else(|e| vec![Some(Err((error_code(&e), e))); count]);
+
+    let mut first_error = None;
+    for (slot, outcome) in results.iter_mut().zip(outcomes) {
+        slot.out_ptr = std::ptr::null_mut();
+        slot.out_len = 0;
+        slot.rc = match outcome {
+            Some(Ok(data)) => write_output(data, &mut slot.out_ptr, &mut slot.out_len),
+            Some(Err((code, err))) => {
+                first_error.get_or_insert((code, err));
+                code
+            }
+            None => ERR_CANCELLED,
+        };
+    }
+
+    match first_error {
+        Some((code, err)) => fail(code, err),
+        None => ERR_SUCCESS,
+    }
+}
+
+/// Get the message of the last failing call on the calling thread
+///
+/// On success `*out_ptr`/`*out_len` receive a UTF-8 message buffer that
+/// must be released with `rrpc_free()`. If no error is recorded, `*out_ptr`
+/// is set to null and `*out_len` to 0.
+///
+/// # Safety
+/// `out_ptr` and `out_len` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_last_error(out_ptr: *mut *mut u8, out_len: *mut usize) -> c_int {
+    if out_ptr.is_null() || out_len.is_null() {
+        return ERR_INTERNAL;
+    }
+
+    match last_error() {
+        Some(err) => write_output(err.to_string().into_bytes(), out_ptr, out_len),
+        None => {
+            *out_ptr = std::ptr::null_mut();
+            *out_len = 0;
+            ERR_SUCCESS
+        }
+    }
+}
+
+/// Clear the last error recorded on the calling thread
+#[no_mangle]
+pub extern "C" fn rrpc_clear_error() {
+    clear_last_error();
+}
+
+/// Free memory allocated by `rrpc_call` or `rrpc_last_error`
+///
+/// The allocation is rebuilt from the length and capacity recorded when it
+/// was handed out, so a `len` other than the one returned alongside `ptr`
+/// is logged as an error but still frees the buffer correctly. Pointers
+/// that are not live buffers, including ones already freed, are logged and
+/// ignored. Null and the pointer of an empty output are no-ops.
+///
+/// # Safety
+/// Pointer must not be used after calling this function.
+#[no_mangle]
+pub unsafe extern "C" fn rrpc_free(ptr: *mut u8, len: usize) {
+    if ptr.is_null() || ptr == empty_output() {
+        return;
+    }
+    let Some((actual, capacity)) = LIVE_BUFFERS.lock().remove(&(ptr as usize)) else {
+        log::error!("rrpc_free: {:p} is not a live output buffer; ignored", ptr);
+        return;
+    };
+    if len != actual {
+        log::error!("rrpc_free: buffer {:p} has length {}, freed with length {}", ptr, actual, len);
+    }
+    drop(Vec::from_raw_parts(ptr, actual, capacity));
+}
+
+/// Pointer handed out for empty outputs
+fn empty_output() -> *mut u8 {
+    std::ptr::addr_of!(EMPTY_OUTPUT).cast_mut()
+}
+
+/// Get the default runtime created by `rrpc_init` (for registration/advanced usage)
+///
+/// Returns `None` before `rrpc_init` and after `rrpc_shutdown`.
+pub fn default_runtime() -> Option<Arc<Runtime>> {
+    DEFAULT_RUNTIME.read().clone()
+}
+
+/// Get the global registry (for testing/advanced usage)
+///
+/// Returns `None` before `rrpc_init` and after `rrpc_shutdown`.
+#[deprecated(note = "use `default_runtime()` and `Runtime::registry_mut`")]
+pub fn get_registry() -> Option<&'static DefaultRegistry> {
+    static DEFAULT_REGISTRY: DefaultRegistry = DefaultRegistry { _private: () };
+    default_runtime().map(|_| &DEFAULT_REGISTRY)
+}
+
+/// The default runtime's registry, as returned by the deprecated `get_registry`
+pub struct DefaultRegistry {
+    _private: (),
+}
+
+impl DefaultRegistry {
+    /// Edit the registry; changes are published when the guard is dropped
+    ///
+    /// # Panics
+    /// If there is no default runtime, i.e. after `rrpc_shutdown` and
+    /// before the next `rrpc_init`.
+    pub fn lock(&self) -> DefaultRegistryGuard {
+        let runtime = default_runtime().expect("rrpc_init has not been called");
+        // SAFETY: the guard borrows the runtime's heap allocation, which never
+        // moves and which `_runtime` keeps alive; `guard` is declared first,
+        // so it is dropped before `_runtime`
+        let guard = unsafe {
+            std::mem::transmute::<runtime::RegistryGuard<'_>, runtime::RegistryGuard<'static>>(runtime.registry_mut())
+        };
+        DefaultRegistryGuard { guard, _runtime: runtime }
+    }
+}
+
+/// Write access to the default runtime's registry, from `DefaultRegistry::lock`
+pub struct DefaultRegistryGuard {
+    guard: runtime::RegistryGuard<'static>,
+    _runtime: Arc<Runtime>,
+}
+
+impl Deref for DefaultRegistryGuard {
+    type Target = Registry;
+
+    fn deref(&self) -> &Registry {
+        &self.guard
+    }
+}
+
+impl DerefMut for DefaultRegistryGuard {
+    fn deref_mut(&mut self) -> &mut Registry {
+        &mut self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_init() {
+        unsafe {
+            let result = rrpc_init();
+            assert_eq!(result, ERR_SUCCESS);
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_registry() {
+        unsafe { rrpc_init() };
+        
+        let registry = get_registry().unwrap();
+        let mut reg = registry.lock();
+        
+        reg.register("test", |input| Ok(input.to_vec()));
+        
+        let result = reg.call("test", b"hello").unwrap();
+        assert_eq!(result, b"hello");
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn get_registry_guard_outlives_the_temporary() {
+        unsafe { rrpc_init() };
+
+        {
+            let mut reg = get_registry().unwrap().lock();
+            reg.register("ffi_via_get_registry", |_| Ok(b"shim".to_vec()));
+        }
+        let registry = default_runtime().unwrap().registry();
+        assert_eq!(registry.call("ffi_via_get_registry", b"").unwrap(), b"shim");
+    }
+
+    #[test]
+    fn rrpc_call_null_method_returns_parse_error() {
+        unsafe { rrpc_init(); }
+
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe { rrpc_call(std::ptr::null::<c_char>(), b"".as_ptr(), 0, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_PARSE_ERROR);
+    }
+
+    #[test]
+    #[allow(clippy::manual_c_str_literals)]
+    fn rrpc_call_oversized_input_returns_too_large() {
+        unsafe { rrpc_init(); }
+        // construct a dummy buffer larger than MAX_INPUT_LEN (10MB)
+        let large_size = 10 * 1024 * 1024 + 1;
+        let vec = vec![0u8; large_size];
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe { rrpc_call("test\0".as_ptr() as *const c_char, vec.as_ptr(), vec.len(), &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_TOO_LARGE);
+    }
+
+    #[test]
+    #[allow(clippy::manual_c_str_literals)]
+    fn rrpc_call_unknown_method_returns_unknown() {
         unsafe { rrpc_init(); }
         let mut out_ptr: *mut u8 = std::ptr::null_mut();
         let mut out_len: usize = 0;
         let rc = unsafe { rrpc_call("no_such_method\0".as_ptr() as *const c_char, b"".as_ptr(), 0, &mut out_ptr, &mut out_len) };
         assert_eq!(rc, ERR_UNKNOWN_METHOD);
     }
+
+    #[test]
+    fn rrpc_call_panicking_handler_returns_panic() {
+        unsafe { rrpc_init(); }
+        default_runtime().unwrap().registry_mut().register("ffi_panics", |_| panic!("handler blew up"));
+        default_runtime().unwrap().registry_mut().register("ffi_echo", |input| Ok(input.to_vec()));
+
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe { rrpc_call(c"ffi_panics".as_ptr(), b"".as_ptr(), 0, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_PANIC);
+        assert!(matches!(last_error(), Some(RpcError::Panic(ref m)) if m == "handler blew up"));
+
+        // Global registry is still usable afterwards
+        let rc = unsafe { rrpc_call(c"ffi_echo".as_ptr(), b"hi".as_ptr(), 2, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_SUCCESS);
+        assert_eq!(unsafe { std::slice::from_raw_parts(out_ptr, out_len) }, b"hi");
+        unsafe { rrpc_free(out_ptr, out_len) };
+    }
+
+    #[test]
+    fn rrpc_call_hands_off_handler_allocation() {
+        static HANDLER_PTR: AtomicUsize = AtomicUsize::new(0);
+
+        unsafe { rrpc_init(); }
+        default_runtime().unwrap().registry_mut().register("ffi_exact_vec", |_| {
+            let out = vec![7u8; 4096];
+            HANDLER_PTR.store(out.as_ptr() as usize, Ordering::SeqCst);
+            Ok(out)
+        });
+
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe { rrpc_call(c"ffi_exact_vec".as_ptr(), b"".as_ptr(), 0, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_SUCCESS);
+        assert_eq!(out_ptr as usize, HANDLER_PTR.load(Ordering::SeqCst));
+        assert_eq!(out_len, 4096);
+        unsafe { rrpc_free(out_ptr, out_len) };
+    }
+
+    #[test]
+    fn rrpc_call_hands_off_spare_capacity_and_tolerates_bad_frees() {
+        static HANDLER_PTR: AtomicUsize = AtomicUsize::new(0);
+        const N: usize = 1000;
+
+        unsafe { rrpc_init(); }
+        default_runtime().unwrap().registry_mut().register("ffi_spare_vec", |_| {
+            let mut out = Vec::with_capacity(N * 2);
+            out.extend(std::iter::repeat_n(3u8, N));
+            HANDLER_PTR.store(out.as_ptr() as usize, Ordering::SeqCst);
+            Ok(out)
+        });
+        default_runtime().unwrap().registry_mut().register("ffi_empty", |_| Ok(Vec::with_capacity(64)));
+
+        let call = |method: &CStr| {
+            let mut out_ptr: *mut u8 = std::ptr::null_mut();
+            let mut out_len: usize = 0;
+            let rc = unsafe { rrpc_call(method.as_ptr(), b"".as_ptr(), 0, &mut out_ptr, &mut out_len) };
+            assert_eq!(rc, ERR_SUCCESS);
+            (out_ptr, out_len)
+        };
+
+        // Not shrunk, so not copied
+        let (out_ptr, out_len) = call(c"ffi_spare_vec");
+        assert_eq!(out_ptr as usize, HANDLER_PTR.load(Ordering::SeqCst));
+        assert_eq!(out_len, N);
+        assert_eq!(LIVE_BUFFERS.lock().get(&(out_ptr as usize)), Some(&(N, N * 2)));
+
+        // A wrong length still frees the real allocation; a second free is ignored
+        unsafe { rrpc_free(out_ptr, 1) };
+        assert!(!LIVE_BUFFERS.lock().contains_key(&(out_ptr as usize)));
+        unsafe { rrpc_free(out_ptr, out_len) };
+
+        // Empty outputs are not null, and freeing them is harmless
+        let (out_ptr, out_len) = call(c"ffi_empty");
+        assert!(!out_ptr.is_null());
+        assert_eq!(out_len, 0);
+        unsafe { rrpc_free(out_ptr, out_len) };
+    }
+
+    #[test]
+    fn rrpc_last_error_reports_handler_message() {
+        unsafe { rrpc_init(); }
+        default_runtime().unwrap().registry_mut().register("ffi_not_found", |_| Err(RpcError::NotFound("user 42".into())));
+
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe { rrpc_call(c"ffi_not_found".as_ptr(), b"".as_ptr(), 0, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_NOT_FOUND);
+
+        let rc = unsafe { rrpc_last_error(&mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_SUCCESS);
+        assert_eq!(unsafe { std::slice::from_raw_parts(out_ptr, out_len) }, b"Not found: user 42");
+        unsafe { rrpc_free(out_ptr, out_len) };
+
+        rrpc_clear_error();
+        let rc = unsafe { rrpc_last_error(&mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_SUCCESS);
+        assert!(out_ptr.is_null());
+        assert_eq!(out_len, 0);
+    }
+
+    #[test]
+    fn rrpc_call_rt_uses_handle_registry() {
+        let rt = rrpc_runtime_new();
+        unsafe { &*rt }.registry_mut().register("rt_only", |_| Ok(b"rt".to_vec()));
+
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe { rrpc_call_rt(rt, c"rt_only".as_ptr(), std::ptr::null(), 0, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_SUCCESS);
+        assert_eq!(unsafe { std::slice::from_raw_parts(out_ptr, out_len) }, b"rt");
+        unsafe { rrpc_free(out_ptr, out_len) };
+
+        // Not visible through the default runtime
+        unsafe { rrpc_init(); }
+        let rc = unsafe { rrpc_call(c"rt_only".as_ptr(), std::ptr::null(), 0, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_UNKNOWN_METHOD);
+
+        unsafe { rrpc_runtime_free(rt) };
+    }
+
+    #[test]
+    fn rrpc_call_reentrant_handler() {
+        unsafe { rrpc_init(); }
+        {
+            let rt = default_runtime().unwrap();
+            let mut reg = rt.registry_mut();
+            reg.register("ffi_inner", |input| Ok(input.iter().rev().copied().collect()));
+            reg.register_with_ctx("ffi_outer", |ctx, input| ctx.call("ffi_inner", input));
+        }
+
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe { rrpc_call(c"ffi_outer".as_ptr(), b"abc".as_ptr(), 3, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_SUCCESS);
+        assert_eq!(unsafe { std::slice::from_raw_parts(out_ptr, out_len) }, b"cba");
+        unsafe { rrpc_free(out_ptr, out_len) };
+    }
+
+    type Completion = std::sync::mpsc::Sender<(u64, c_int, Vec<u8>)>;
+
+    /// Takes ownership of a boxed `Completion`, so the test may return while the send is still finishing
+    extern "C" fn send_completion(call_id: u64, rc: c_int, out_ptr: *mut u8, out_len: usize, user_data: *mut c_void) {
+        let tx = unsafe { Box::from_raw(user_data as *mut Completion) };
+        let out = if out_ptr.is_null() {
+            Vec::new()
+        } else {
+            let out = unsafe { std::slice::from_raw_parts(out_ptr, out_len) }.to_vec();
+            unsafe { rrpc_free(out_ptr, out_len) };
+            out
+        };
+        tx.send((call_id, rc, out)).unwrap();
+    }
+
+    #[test]
+    fn rrpc_call_async_invokes_callback() {
+        unsafe { rrpc_init(); }
+        default_runtime().unwrap().registry_mut().register("ffi_async_echo", |input| Ok(input.to_vec()));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let user_data = || Box::into_raw(Box::<Completion>::new(tx.clone())) as *mut c_void;
+
+        let id = unsafe { rrpc_call_async(c"ffi_async_echo".as_ptr(), b"ping".as_ptr(), 4, Some(send_completion), user_data()) };
+        assert_ne!(id, 0);
+        let (done_id, rc, out) = rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert_eq!((done_id, rc, out.as_slice()), (id, ERR_SUCCESS, b"ping".as_slice()));
+
+        let id = unsafe { rrpc_call_async(c"ffi_async_missing".as_ptr(), std::ptr::null(), 0, Some(send_completion), user_data()) };
+        let (done_id, rc, _) = rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert_eq!((done_id, rc), (id, ERR_UNKNOWN_METHOD));
+    }
+
+    #[test]
+    fn rrpc_call_async_null_callback_is_rejected() {
+        unsafe { rrpc_init(); }
+        let id = unsafe { rrpc_call_async(c"echo".as_ptr(), std::ptr::null(), 0, None, std::ptr::null_mut()) };
+        assert_eq!(id, 0);
+        assert!(matches!(last_error(), Some(RpcError::Internal(_))));
+    }
+
+    extern "C" fn collect_item(item_ptr: *const u8, item_len: usize, user_data: *mut c_void) -> c_int {
+        let frames = unsafe { &mut *(user_data as *mut Vec<Vec<u8>>) };
+        frames.push(unsafe { std::slice::from_raw_parts(item_ptr, item_len) }.to_vec());
+        0
+    }
+
+    extern "C" fn record_done(rc: c_int, user_data: *mut c_void) {
+        let frames = unsafe { &mut *(user_data as *mut Vec<Vec<u8>>) };
+        frames.push(format!("done:{}", rc).into_bytes());
+    }
+
+    #[test]
+    fn rrpc_call_stream_forwards_frames_then_done() {
+        unsafe { rrpc_init(); }
+        default_runtime().unwrap().registry_mut().register_stream("ffi_stream", |_, input, sink| {
+            for chunk in input.chunks(2) {
+                sink.send(chunk)?;
+            }
+            Err(RpcError::NotFound("tail".into()))
+        });
+
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        let user_data = &mut frames as *mut _ as *mut c_void;
+        let rc = unsafe {
+            rrpc_call_stream(c"ffi_stream".as_ptr(), b"abcde".as_ptr(), 5, Some(collect_item), Some(record_done), user_data)
+        };
+
+        assert_eq!(rc, ERR_NOT_FOUND);
+        let expected: Vec<&[u8]> = vec![b"ab", b"cd", b"e", b"done:4"];
+        assert_eq!(frames, expected);
+    }
+
+    #[test]
+    fn rrpc_call_with_token_returns_cancelled() {
+        unsafe { rrpc_init(); }
+        default_runtime().unwrap().registry_mut().register_with_ctx("ffi_wait_cancel", |ctx, _| {
+            while !ctx.is_cancelled() {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            Ok(b"unreachable".to_vec())
+        });
+
+        let token = rrpc_cancel_token_new();
+        let token_addr = token as usize;
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            unsafe { rrpc_cancel(token_addr as *const CancelToken) };
+        });
+
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe {
+            rrpc_call_with_token(c"ffi_wait_cancel".as_ptr(), std::ptr::null(), 0, token, &mut out_ptr, &mut out_len)
+        };
+        canceller.join().unwrap();
+
+        assert_eq!(rc, ERR_CANCELLED);
+        assert!(matches!(last_error(), Some(RpcError::Cancelled)));
+        unsafe { rrpc_cancel_token_free(token) };
+    }
+
+    #[test]
+    fn rrpc_call_with_timeout_returns_timeout() {
+        unsafe { rrpc_init(); }
+        default_runtime().unwrap().registry_mut().register("ffi_sleepy", |_| {
+            std::thread::sleep(std::time::Duration::from_secs(2));
+            Ok(vec![])
+        });
+
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let start = std::time::Instant::now();
+        let rc = unsafe {
+            rrpc_call_with_timeout(c"ffi_sleepy".as_ptr(), std::ptr::null(), 0, 30, std::ptr::null(), &mut out_ptr, &mut out_len)
+        };
+
+        assert_eq!(rc, ERR_TIMEOUT);
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn rrpc_call_into_fills_caller_buffer() {
+        unsafe { rrpc_init(); }
+        default_runtime().unwrap().registry_mut().register("ffi_into_echo", |input| Ok(input.to_vec()));
+
+        let mut buf = [0u8; 8];
+        let mut out_len: usize = 0;
+        let rc = unsafe {
+            rrpc_call_into(c"ffi_into_echo".as_ptr(), b"hello".as_ptr(), 5, buf.as_mut_ptr(), buf.len(), &mut out_len)
+        };
+        assert_eq!(rc, ERR_SUCCESS);
+        assert_eq!(&buf[..out_len], b"hello");
+
+        let mut small = [0u8; 2];
+        let rc = unsafe {
+            rrpc_call_into(c"ffi_into_echo".as_ptr(), b"hello".as_ptr(), 5, small.as_mut_ptr(), small.len(), &mut out_len)
+        };
+        assert_eq!(rc, ERR_BUFFER_TOO_SMALL);
+        assert_eq!(out_len, 5);
+        assert_eq!(small, [0, 0]);
+
+        // Size query with no buffer
+        let rc = unsafe {
+            rrpc_call_into(c"ffi_into_echo".as_ptr(), b"abc".as_ptr(), 3, std::ptr::null_mut(), 0, &mut out_len)
+        };
+        assert_eq!(rc, ERR_BUFFER_TOO_SMALL);
+        assert_eq!(out_len, 3);
+    }
+
+    #[test]
+    fn rrpc_call_rt_null_handle_returns_not_initialized() {
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe { rrpc_call_rt(std::ptr::null(), c"echo".as_ptr(), std::ptr::null(), 0, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_NOT_INITIALIZED);
+    }
+
+    #[test]
+    fn rrpc_call_batch_reports_per_entry_results() {
+        unsafe { rrpc_init(); }
+        default_runtime().unwrap().registry_mut().register("ffi_batch_echo", |input| Ok(input.to_vec()));
+
+        let entry = |method: &std::ffi::CStr, input: &'static [u8]| BatchEntry {
+            method: method.as_ptr(),
+            in_ptr: input.as_ptr(),
+            in_len: input.len(),
+        };
+        let entries = [
+            entry(c"ffi_batch_echo", b"one"),
+            entry(c"no_such_method", b""),
+            entry(c"ffi_batch_echo", b"three"),
+        ];
+        let empty = || BatchResult { rc: -1, out_ptr: std::ptr::null_mut(), out_len: 0 };
+
+        for flags in [0, BATCH_PARALLEL] {
+            let mut results = [empty(), empty(), empty()];
+            let rc = unsafe { rrpc_call_batch(entries.as_ptr(), entries.len(), flags, results.as_mut_ptr()) };
+            assert_eq!(rc, ERR_UNKNOWN_METHOD);
+            assert!(matches!(last_error(), Some(RpcError::UnknownMethod(ref m)) if m == "no_such_method"));
+
+            assert_eq!(results[0].rc, ERR_SUCCESS);
+            assert_eq!(unsafe { std::slice::from_raw_parts(results[0].out_ptr, results[0].out_len) }, b"one");
+            assert_eq!(results[1].rc, ERR_UNKNOWN_METHOD);
+            assert!(results[1].out_ptr.is_null());
+            assert_eq!(results[2].rc, ERR_SUCCESS);
+            assert_eq!(unsafe { std::slice::from_raw_parts(results[2].out_ptr, results[2].out_len) }, b"three");
+            for result in &results {
+                unsafe { rrpc_free(result.out_ptr, result.out_len) };
+            }
+        }
+
+        let mut results = [empty(), empty(), empty()];
+        let rc = unsafe { rrpc_call_batch(entries.as_ptr(), entries.len(), BATCH_STOP_ON_ERROR, results.as_mut_ptr()) };
+        assert_eq!(rc, ERR_UNKNOWN_METHOD);
+        assert_eq!(results[2].rc, ERR_CANCELLED);
+        assert!(results[2].out_ptr.is_null());
+        unsafe { rrpc_free(results[0].out_ptr, results[0].out_len) };
+    }
+
+    #[test]
+    fn rrpc_call_batch_entries_are_counted_and_intercepted() {
+        let rt = rrpc_runtime_new();
+        let seen = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        {
+            let seen = Arc::clone(&seen);
+            let mut reg = unsafe { &*rt }.registry_mut();
+            reg.register("echo", |input| Ok(input.to_vec()));
+            reg.intercept("*", move |ctx, input, next| {
+                seen.lock().push(ctx.method().to_string());
+                next.run(input)
+            });
+        }
+
+        let entries = [
+            BatchEntry { method: c"echo".as_ptr(), in_ptr: b"a".as_ptr(), in_len: 1 },
+            BatchEntry { method: c"echo".as_ptr(), in_ptr: b"bc".as_ptr(), in_len: 2 },
+        ];
+        let mut results = [(); 2].map(|_| BatchResult { rc: -1, out_ptr: std::ptr::null_mut(), out_len: 0 });
+        for flags in [0, BATCH_PARALLEL] {
+            let rc = unsafe { rrpc_call_batch_rt(rt, entries.as_ptr(), entries.len(), flags, results.as_mut_ptr()) };
+            assert_eq!(rc, ERR_SUCCESS);
+            for result in &results {
+                unsafe { rrpc_free(result.out_ptr, result.out_len) };
+            }
+        }
+
+        assert_eq!(*seen.lock(), ["echo"; 4]);
+        let stats = unsafe { &*rt }.registry().stats();
+        assert_eq!((stats[0].name.as_str(), stats[0].calls), ("echo", 4));
+        unsafe { rrpc_runtime_free(rt) };
+    }
+
+    #[test]
+    fn rrpc_list_methods_and_has_method() {
+        let rt = rrpc_runtime_new();
+        {
+            let mut reg = unsafe { &*rt }.registry_mut();
+            reg.register("b.echo", |input| Ok(input.to_vec())).description("Echo").deprecated();
+            reg.register_stream("a.list", |_, _, _| Ok(()));
+        }
+
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe { rrpc_list_methods_rt(rt, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_SUCCESS);
+        let json = std::str::from_utf8(unsafe { std::slice::from_raw_parts(out_ptr, out_len) }).unwrap();
+        assert_eq!(
+            json,
+            concat!(
+                r#"[{"name":"a.list","kind":"stream","description":null,"input_type":null,"output_type":null,"deprecated":false,"timeout_ms":null,"requires":[],"audited":false},"#,
+                r#"{"name":"b.echo","kind":"unary","description":"Echo","input_type":null,"output_type":null,"deprecated":true,"timeout_ms":null,"requires":[],"audited":false}]"#
+            )
+        );
+        unsafe { rrpc_free(out_ptr, out_len) };
+
+        assert_eq!(unsafe { rrpc_has_method_rt(rt, c"b.echo".as_ptr()) }, 1);
+        assert_eq!(unsafe { rrpc_has_method_rt(rt, c"missing".as_ptr()) }, 0);
+        assert_eq!(unsafe { rrpc_has_method_rt(rt, std::ptr::null()) }, 0);
+        assert!(matches!(last_error(), Some(RpcError::ParseError(_))));
+
+        unsafe { rrpc_runtime_free(rt) };
+    }
+
+    #[test]
+    fn rrpc_stats_rt_reports_calls() {
+        let rt = rrpc_runtime_new();
+        unsafe { &*rt }.registry_mut().register("echo", |input| Ok(input.to_vec()));
+
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe { rrpc_call_rt(rt, c"echo".as_ptr(), b"abc".as_ptr(), 3, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_SUCCESS);
+        unsafe { rrpc_free(out_ptr, out_len) };
+
+        let rc = unsafe { rrpc_stats_rt(rt, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_SUCCESS);
+        let json = std::str::from_utf8(unsafe { std::slice::from_raw_parts(out_ptr, out_len) }).unwrap();
+        assert!(
+            json.contains(r#""methods":[{"name":"echo","calls":1,"errors":{},"bytes_in":3,"bytes_out":3,"#),
+            "{}",
+            json
+        );
+        unsafe { rrpc_free(out_ptr, out_len) };
+
+        assert_eq!(unsafe { rrpc_stats_rt(rt, std::ptr::null_mut(), &mut out_len) }, ERR_INTERNAL);
+        unsafe { rrpc_runtime_free(rt) };
+    }
+
+    #[test]
+    fn rrpc_set_log_callback_forwards_filtered_records() {
+        static RECORDS: parking_lot::Mutex<Vec<(c_int, String, String)>> = parking_lot::Mutex::new(Vec::new());
+
+        extern "C" fn collect(
+            level: c_int,
+            target_ptr: *const u8,
+            target_len: usize,
+            msg_ptr: *const u8,
+            msg_len: usize,
+            user_data: *mut c_void,
+        ) {
+            assert_eq!(user_data as usize, 7);
+            let text = |ptr, len| String::from_utf8(unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec()).unwrap();
+            let target = text(target_ptr, target_len);
+            if target == "rrpc_log_test" {
+                RECORDS.lock().push((level, target, text(msg_ptr, msg_len)));
+            }
+        }
+
+        assert_eq!(unsafe { rrpc_set_log_callback(Some(collect), 7 as *mut c_void) }, ERR_SUCCESS);
+        assert_eq!(rrpc_set_log_level(LOG_WARN), ERR_SUCCESS);
+        log::info!(target: "rrpc_log_test", "hidden");
+        log::warn!(target: "rrpc_log_test", "shown {}", 1);
+
+        assert_eq!(rrpc_set_log_level(LOG_DEBUG), ERR_SUCCESS);
+        log::debug!(target: "rrpc_log_test", "shown {}", 2);
+        assert_eq!(rrpc_set_log_level(9), ERR_INTERNAL);
+
+        assert_eq!(unsafe { rrpc_set_log_callback(None, std::ptr::null_mut()) }, ERR_SUCCESS);
+        log::error!(target: "rrpc_log_test", "after clearing");
+
+        assert_eq!(
+            *RECORDS.lock(),
+            [
+                (LOG_WARN, "rrpc_log_test".to_string(), "shown 1".to_string()),
+                (LOG_DEBUG, "rrpc_log_test".to_string(), "shown 2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rrpc_call_with_capabilities_enforces_requirements() {
+        let rt = rrpc_runtime_new();
+        unsafe { &*rt }.registry_mut().register("echo", |input| Ok(input.to_vec()));
+        unsafe { &*rt }.registry_mut().register("sys.kill", |_| Ok(b"killed".to_vec()));
+        unsafe { &*rt }
+            .registry_mut()
+            .register_with_ctx("proxy", |ctx, input| ctx.call("sys.kill", input));
+        let rc = unsafe { rrpc_require_capability_rt(rt, c"sys.*".as_ptr(), c"process.terminate".as_ptr()) };
+        assert_eq!(rc, ERR_SUCCESS);
+
+        let token = rrpc_capability_token_new();
+        let call = |method: &std::ffi::CStr, token: *const CapabilityToken| {
+            let mut out_ptr: *mut u8 = std::ptr::null_mut();
+            let mut out_len: usize = 0;
+            let rc = unsafe {
+                rrpc_call_with_capabilities_rt(rt, method.as_ptr(), b"x".as_ptr(), 1, token, &mut out_ptr, &mut out_len)
+            };
+            unsafe { rrpc_free(out_ptr, out_len) };
+            rc
+        };
+
+        assert_eq!(call(c"echo", token), ERR_SUCCESS);
+        assert_eq!(call(c"sys.kill", token), ERR_PERMISSION_DENIED);
+        assert!(matches!(last_error(), Some(RpcError::PermissionDenied(m)) if m.contains("process.terminate")));
+        let stats = unsafe { &*rt }.registry().stats();
+        let kill = stats.iter().find(|s| s.name == "sys.kill").unwrap();
+        assert_eq!((kill.calls, kill.errors.as_slice()), (1, &[("permission_denied", 1)][..]));
+        // A permitted method cannot reach a forbidden one on the caller's behalf
+        assert_eq!(call(c"proxy", token), ERR_PERMISSION_DENIED);
+
+        // Without a token the runtime's default grants apply
+        assert_eq!(call(c"sys.kill", std::ptr::null()), ERR_PERMISSION_DENIED);
+        assert_eq!(unsafe { rrpc_grant_default_rt(rt, c"process.*".as_ptr()) }, ERR_SUCCESS);
+        assert_eq!(call(c"sys.kill", std::ptr::null()), ERR_SUCCESS);
+        assert_eq!(call(c"sys.kill", token), ERR_PERMISSION_DENIED);
+
+        assert_eq!(unsafe { rrpc_grant(token, c"process.terminate".as_ptr()) }, ERR_SUCCESS);
+        assert_eq!(call(c"proxy", token), ERR_SUCCESS);
+        assert_eq!(unsafe { rrpc_grant(std::ptr::null(), c"x".as_ptr()) }, ERR_PARSE_ERROR);
+
+        unsafe { rrpc_capability_token_free(token) };
+        unsafe { rrpc_runtime_free(rt) };
+    }
+
+    #[test]
+    fn rrpc_call_batch_with_capabilities_checks_every_entry() {
+        let rt = rrpc_runtime_new();
+        unsafe { &*rt }.registry_mut().register("echo", |input| Ok(input.to_vec()));
+        unsafe { &*rt }.registry_mut().register("sys.kill", |_| Ok(b"killed".to_vec()));
+        let rc = unsafe { rrpc_require_capability_rt(rt, c"sys.*".as_ptr(), c"process.terminate".as_ptr()) };
+        assert_eq!(rc, ERR_SUCCESS);
+        assert_eq!(unsafe { rrpc_grant_default_rt(rt, c"*".as_ptr()) }, ERR_SUCCESS);
+
+        let entries = [
+            BatchEntry { method: c"echo".as_ptr(), in_ptr: b"x".as_ptr(), in_len: 1 },
+            BatchEntry { method: c"sys.kill".as_ptr(), in_ptr: std::ptr::null(), in_len: 0 },
+        ];
+        let batch = |token: *const CapabilityToken| {
+            let mut results = [(); 2].map(|_| BatchResult { rc: -1, out_ptr: std::ptr::null_mut(), out_len: 0 });
+            let rc = unsafe {
+                rrpc_call_batch_with_capabilities_rt(rt, entries.as_ptr(), entries.len(), 0, token, results.as_mut_ptr())
+            };
+            for result in &results {
+                unsafe { rrpc_free(result.out_ptr, result.out_len) };
+            }
+            (rc, results.map(|result| result.rc))
+        };
+
+        let token = rrpc_capability_token_new();
+        assert_eq!(batch(token), (ERR_PERMISSION_DENIED, [ERR_SUCCESS, ERR_PERMISSION_DENIED]));
+        assert_eq!(unsafe { rrpc_grant(token, c"process.terminate".as_ptr()) }, ERR_SUCCESS);
+        assert_eq!(batch(token), (ERR_SUCCESS, [ERR_SUCCESS, ERR_SUCCESS]));
+        // Without a token the runtime's default grants apply
+        assert_eq!(batch(std::ptr::null()), (ERR_SUCCESS, [ERR_SUCCESS, ERR_SUCCESS]));
+
+        unsafe { rrpc_capability_token_free(token) };
+        unsafe { rrpc_runtime_free(rt) };
+    }
+
+    #[test]
+    fn rrpc_audit_file_records_and_verifies_calls() {
+        const KEY: &[u8] = b"host secret";
+        let path = std::env::temp_dir().join(format!("rrpc-audit-ffi-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+
+        let rt = rrpc_runtime_new();
+        unsafe { &*rt }.registry_mut().register("sys.kill", |_| Ok(vec![]));
+        unsafe { &*rt }.registry_mut().register("echo", |input| Ok(input.to_vec()));
+        assert_eq!(unsafe { rrpc_audit_methods_rt(rt, c"sys.*".as_ptr()) }, ERR_SUCCESS);
+        assert_eq!(unsafe { rrpc_audit_file_rt(rt, c_path.as_ptr(), std::ptr::null(), 0) }, ERR_PARSE_ERROR);
+        assert_eq!(unsafe { rrpc_audit_file_rt(rt, c_path.as_ptr(), KEY.as_ptr(), KEY.len()) }, ERR_SUCCESS);
+
+        let token = unsafe { rrpc_capability_token_new_named(c"script".as_ptr()) };
+        for method in [c"sys.kill", c"echo"] {
+            let mut out_ptr: *mut u8 = std::ptr::null_mut();
+            let mut out_len: usize = 0;
+            let rc = unsafe {
+                rrpc_call_with_capabilities_rt(rt, method.as_ptr(), b"x".as_ptr(), 1, token, &mut out_ptr, &mut out_len)
+            };
+            assert_eq!(rc, ERR_SUCCESS);
+            unsafe { rrpc_free(out_ptr, out_len) };
+        }
+        unsafe { rrpc_capability_token_free(token) };
+        assert_eq!(unsafe { rrpc_audit_file_rt(rt, std::ptr::null(), std::ptr::null(), 0) }, ERR_SUCCESS);
+        unsafe { rrpc_runtime_free(rt) };
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(log.lines().count(), 1);
+        assert!(log.contains(r#""method":"sys.kill","caller":"script","#), "{}", log);
+
+        let verify = |key: &[u8]| {
+            let (mut records, mut broken) = (0, 0);
+            let rc = unsafe { rrpc_audit_verify(c_path.as_ptr(), key.as_ptr(), key.len(), &mut records, &mut broken) };
+            assert_eq!(rc, ERR_SUCCESS);
+            (records, broken)
+        };
+        assert_eq!(verify(KEY), (1, 0));
+        assert_eq!(verify(b"wrong key"), (0, 1));
+
+        std::fs::write(&path, log.replace("sys.kill", "sys.ping")).unwrap();
+        assert_eq!(verify(KEY), (0, 1));
+        let rt = rrpc_runtime_new();
+        assert_eq!(unsafe { rrpc_audit_file_rt(rt, c_path.as_ptr(), KEY.as_ptr(), KEY.len()) }, ERR_INTERNAL);
+        unsafe { rrpc_runtime_free(rt) };
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// ABI compatibility policy (docs/api-reference.md, "ABI Versioning"):
+    /// every export, struct and code of the current major version is pinned
+    /// here. Changing one breaks this test and requires a major bump;
+    /// additions must extend it and bump the minor version.
+    #[test]
+    fn abi_surface_is_pinned_to_version() {
+        type Out = *mut *mut u8;
+        type Len = *mut usize;
+        type Rt = *const Runtime;
+        type Name = *const c_char;
+        type In = *const u8;
+
+        let _: extern "C" fn() -> u32 = rrpc_abi_version;
+        let _: extern "C" fn() -> u64 = rrpc_features;
+        let _: unsafe extern "C" fn() -> c_int = rrpc_init;
+        let _: extern "C" fn() -> *mut Runtime = rrpc_runtime_new;
+        let _: unsafe extern "C" fn(*mut Runtime) = rrpc_runtime_free;
+        let _: unsafe extern "C" fn(Name, In, usize, Out, Len) -> c_int = rrpc_call;
+        let _: unsafe extern "C" fn(Rt, Name, In, usize, Out, Len) -> c_int = rrpc_call_rt;
+        let _: unsafe extern "C" fn(Name, In, usize, *mut u8, usize, Len) -> c_int = rrpc_call_into;
+        let _: unsafe extern "C" fn(Rt, Name, In, usize, *mut u8, usize, Len) -> c_int = rrpc_call_into_rt;
+        let _: extern "C" fn() -> *mut CancelToken = rrpc_cancel_token_new;
+        let _: unsafe extern "C" fn(*const CancelToken) = rrpc_cancel;
+        let _: unsafe extern "C" fn(*mut CancelToken) = rrpc_cancel_token_free;
+        let _: unsafe extern "C" fn(Name, In, usize, *const CancelToken, Out, Len) -> c_int = rrpc_call_with_token;
+        let _: unsafe extern "C" fn(Rt, Name, In, usize, *const CancelToken, Out, Len) -> c_int =
+            rrpc_call_with_token_rt;
+        let _: unsafe extern "C" fn(Name, In, usize, u64, *const CancelToken, Out, Len) -> c_int =
+            rrpc_call_with_timeout;
+        let _: unsafe extern "C" fn(Rt, Name, In, usize, u64, *const CancelToken, Out, Len) -> c_int =
+            rrpc_call_with_timeout_rt;
+        let _: unsafe extern "C" fn(Name, In, usize, Option<CompletionCallback>, *mut c_void) -> u64 = rrpc_call_async;
+        let _: unsafe extern "C" fn(Rt, Name, In, usize, Option<CompletionCallback>, *mut c_void) -> u64 =
+            rrpc_call_async_rt;
+        let _: unsafe extern "C" fn(Name, In, usize, Option<StreamItemCallback>, Option<StreamDoneCallback>, *mut c_void) -> c_int =
+            rrpc_call_stream;
+        let _: unsafe extern "C" fn(Rt, Name, In, usize, Option<StreamItemCallback>, Option<StreamDoneCallback>, *mut c_void) -> c_int =
+            rrpc_call_stream_rt;
+        let _: unsafe extern "C" fn(Out, Len) -> c_int = rrpc_list_methods;
+        let _: unsafe extern "C" fn(Rt, Out, Len) -> c_int = rrpc_list_methods_rt;
+        let _: unsafe extern "C" fn(Name) -> c_int = rrpc_has_method;
+        let _: unsafe extern "C" fn(Rt, Name) -> c_int = rrpc_has_method_rt;
+        let _: unsafe extern "C" fn(*const BatchEntry, usize, u32, *mut BatchResult) -> c_int = rrpc_call_batch;
+        let _: unsafe extern "C" fn(Rt, *const BatchEntry, usize, u32, *mut BatchResult) -> c_int = rrpc_call_batch_rt;
+        let _: unsafe extern "C" fn(Out, Len) -> c_int = rrpc_last_error;
+        let _: extern "C" fn() = rrpc_clear_error;
+        let _: unsafe extern "C" fn(*mut u8, usize) = rrpc_free;
+
+        assert_eq!(std::mem::size_of::<BatchEntry>(), 3 * std::mem::size_of::<usize>());
+        assert_eq!(std::mem::offset_of!(BatchResult, out_ptr), std::mem::size_of::<usize>());
+        assert_eq!(
+            [ERR_SUCCESS, ERR_NOT_INITIALIZED, ERR_UNKNOWN_METHOD, ERR_PARSE_ERROR, ERR_NOT_FOUND, ERR_SERIALIZATION],
+            [0, 1, 2, 3, 4, 5]
+        );
+        assert_eq!([ERR_TOO_LARGE, ERR_PANIC, ERR_CANCELLED, ERR_TIMEOUT, ERR_BUFFER_TOO_SMALL, ERR_INTERNAL], [6, 7, 8, 9, 10, 99]);
+        assert_eq!(ERR_PLUGIN, 11);
+        assert_eq!(ERR_PERMISSION_DENIED, 12);
+        assert_eq!((BATCH_PARALLEL, BATCH_STOP_ON_ERROR), (1, 2));
+        assert_eq!(rrpc_features(), 0x1_ffff);
+
+        // 1.1
+        let _: unsafe extern "C" fn(*mut usize) -> c_int = rrpc_shutdown;
+        let _: unsafe extern "C" fn(Rt, *mut usize) -> c_int = rrpc_shutdown_rt;
+
+        // 1.2
+        let _: unsafe extern "C" fn(Name, Option<HostMethod>, *mut c_void, Option<HostFreeFn>) -> c_int =
+            rrpc_register_host;
+        let _: unsafe extern "C" fn(Rt, Name, Option<HostMethod>, *mut c_void, Option<HostFreeFn>) -> c_int =
+            rrpc_register_host_rt;
+
+        // 1.3
+        let _: unsafe extern "C" fn(Name, Name) -> c_int = rrpc_load_plugin;
+        let _: unsafe extern "C" fn(Rt, Name, Name) -> c_int = rrpc_load_plugin_rt;
+        let _: unsafe extern "C" fn(Name) -> c_int = rrpc_unload_plugin;
+        let _: unsafe extern "C" fn(Rt, Name) -> c_int = rrpc_unload_plugin_rt;
+        assert_eq!(plugin::FINGERPRINT_SYMBOL, b"rrpc_plugin_fingerprint\0");
+
+        // 1.4
+        let _: unsafe extern "C" fn(Out, Len) -> c_int = rrpc_stats;
+        let _: unsafe extern "C" fn(Rt, Out, Len) -> c_int = rrpc_stats_rt;
+
+        // 1.5
+        let _: unsafe extern "C" fn(Option<LogCallback>, *mut c_void) -> c_int = rrpc_set_log_callback;
+        let _: extern "C" fn(c_int) -> c_int = rrpc_set_log_level;
+        assert_eq!([LOG_OFF, LOG_ERROR, LOG_WARN, LOG_INFO, LOG_DEBUG, LOG_TRACE], [0, 1, 2, 3, 4, 5]);
+
+        // 1.6
+        type Caps = *const CapabilityToken;
+        let _: extern "C" fn() -> *mut CapabilityToken = rrpc_capability_token_new;
+        let _: unsafe extern "C" fn(Caps, Name) -> c_int = rrpc_grant;
+        let _: unsafe extern "C" fn(*mut CapabilityToken) = rrpc_capability_token_free;
+        let _: unsafe extern "C" fn(Name, In, usize, Caps, Out, Len) -> c_int = rrpc_call_with_capabilities;
+        let _: unsafe extern "C" fn(Rt, Name, In, usize, Caps, Out, Len) -> c_int = rrpc_call_with_capabilities_rt;
+        let _: unsafe extern "C" fn(Name, Name) -> c_int = rrpc_require_capability;
+        let _: unsafe extern "C" fn(Rt, Name, Name) -> c_int = rrpc_require_capability_rt;
+        let _: unsafe extern "C" fn(Name) -> c_int = rrpc_grant_default;
+        let _: unsafe extern "C" fn(Rt, Name) -> c_int = rrpc_grant_default_rt;
+
+        // 1.7
+        let _: unsafe extern "C" fn(Name) -> *mut CapabilityToken = rrpc_capability_token_new_named;
+        let _: unsafe extern "C" fn(Name, *const u8, usize) -> c_int = rrpc_audit_file;
+        let _: unsafe extern "C" fn(Rt, Name, *const u8, usize) -> c_int = rrpc_audit_file_rt;
+        let _: unsafe extern "C" fn(Name) -> c_int = rrpc_audit_methods;
+        let _: unsafe extern "C" fn(Rt, Name) -> c_int = rrpc_audit_methods_rt;
+        let _: unsafe extern "C" fn(Name, *const u8, usize, *mut u64, *mut u64) -> c_int = rrpc_audit_verify;
+
+        // 1.8
+        let _: unsafe extern "C" fn(*const BatchEntry, usize, u32, Caps, *mut BatchResult) -> c_int =
+            rrpc_call_batch_with_capabilities;
+        let _: unsafe extern "C" fn(Rt, *const BatchEntry, usize, u32, Caps, *mut BatchResult) -> c_int =
+            rrpc_call_batch_with_capabilities_rt;
+
+        assert_eq!((ABI_VERSION_MAJOR, ABI_VERSION_MINOR), (1, 8));
+        assert_eq!(rrpc_abi_version(), 0x0001_0008);
+    }
+
+    #[test]
+    fn abi_version_is_documented() {
+        let docs = include_str!("../../docs/api-reference.md");
+        let current = format!("Current ABI version: **{}.{}**", ABI_VERSION_MAJOR, ABI_VERSION_MINOR);
+        assert!(docs.contains(&current), "docs/api-reference.md must state \"{}\"", current);
+    }
+
+    #[test]
+    fn rrpc_shutdown_rt_drops_handlers_and_reports_buffers() {
+        let rt = rrpc_runtime_new();
+        let resource = Arc::new(());
+        {
+            let resource = Arc::clone(&resource);
+            unsafe { &*rt }.registry_mut().register("held", move |_| {
+                let _held = &resource;
+                Ok(b"out".to_vec())
+            });
+        }
+
+        let mut out_ptr: *mut u8 = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+        let rc = unsafe { rrpc_call_rt(rt, c"held".as_ptr(), std::ptr::null(), 0, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_SUCCESS);
+
+        let mut outstanding = 0;
+        assert_eq!(unsafe { rrpc_shutdown_rt(rt, &mut outstanding) }, ERR_SUCCESS);
+        // Other tests may hold buffers too; ours is still live
+        assert!(outstanding >= 1);
+        assert_eq!(Arc::strong_count(&resource), 1);
+
+        // The buffer outlives the shutdown
+        assert_eq!(unsafe { std::slice::from_raw_parts(out_ptr, out_len) }, b"out");
+        unsafe { rrpc_free(out_ptr, out_len) };
+
+        let rc = unsafe { rrpc_call_rt(rt, c"held".as_ptr(), std::ptr::null(), 0, &mut out_ptr, &mut out_len) };
+        assert_eq!(rc, ERR_INTERNAL);
+
+        // Batches are tracked like single calls
+        let entry = BatchEntry { method: c"held".as_ptr(), in_ptr: std::ptr::null(), in_len: 0 };
+        let mut result = BatchResult { rc: -1, out_ptr: std::ptr::null_mut(), out_len: 0 };
+        assert_eq!(unsafe { rrpc_call_batch_rt(rt, &entry, 1, 0, &mut result) }, ERR_INTERNAL);
+        assert_eq!(result.rc, ERR_INTERNAL);
+        assert!(result.out_ptr.is_null());
+        assert!(matches!(last_error(), Some(RpcError::Internal(ref m)) if m == "runtime is shut down"));
+
+        // Registrations are rejected too
+        let rc = unsafe { rrpc_register_host_rt(rt, c"late".as_ptr(), Some(host_shout), std::ptr::null_mut(), None) };
+        assert_eq!(rc, ERR_INTERNAL);
+        assert_eq!(unsafe { rrpc_audit_methods_rt(rt, c"*".as_ptr()) }, ERR_INTERNAL);
+        assert!(!unsafe { &*rt }.registry().has_method("late"));
+        unsafe { rrpc_runtime_free(rt) };
+    }
+
+    extern "C" fn host_shout(
+        in_ptr: *const u8,
+        in_len: usize,
+        out_ptr: *mut *mut u8,
+        out_len: *mut usize,
+        user_data: *mut c_void,
+    ) -> c_int {
+        let input = unsafe { std::slice::from_raw_parts(in_ptr, in_len) };
+        let (rc, output) = match std::str::from_utf8(input) {
+            Ok("") => (ERR_NOT_FOUND, b"nothing to shout".to_vec()),
+            Ok(text) => (ERR_SUCCESS, text.to_uppercase().into_bytes()),
+            Err(_) => (ERR_PARSE_ERROR, Vec::new()),
+        };
+        let output = output.into_boxed_slice();
+        unsafe {
+            *out_len = output.len();
+            *out_ptr = Box::into_raw(output) as *mut u8;
+        }
+        unsafe { &*(user_data as *const AtomicUsize) }.fetch_add(1, Ordering::SeqCst);
+        rc
+    }
+
+    extern "C" fn host_free(ptr: *mut u8, len: usize, _user_data: *mut c_void) {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)) });
+    }
+
+    #[test]
+    fn rrpc_register_host_installs_callable_handler() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        let rt = rrpc_runtime_new();
+        let user_data = &CALLS as *const AtomicUsize as *mut c_void;
+        let rc = unsafe { rrpc_register_host_rt(rt, c"host.shout".as_ptr(), Some(host_shout), user_data, Some(host_free)) };
+        assert_eq!(rc, ERR_SUCCESS);
+
+        // Reachable from other handlers through the normal dispatch
+        unsafe { &*rt }
+            .registry_mut()
+            .register_with_ctx("greet", |ctx, input| ctx.call("host.shout", &[b"hi ", input].concat()));
+        let runtime = unsafe { &*rt };
+        assert_eq!(runtime.call("greet", b"there").unwrap(), b"HI THERE");
+        assert!(matches!(runtime.call("host.shout", b""), Err(RpcError::NotFound(ref m)) if m == "nothing to shout"));
+        assert!(matches!(runtime.call("host.shout", &[0xff]), Err(RpcError::ParseError(_))));
+        assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+
+        let rc = unsafe { rrpc_register_host_rt(rt, c"host.none".as_ptr(), None, std::ptr::null_mut(), None) };
+        assert_eq!(rc, ERR_INTERNAL);
+
+        unsafe { rrpc_runtime_free(rt) };
+    }
+
+    #[test]
+    fn rrpc_load_plugin_failures_return_plugin_error() {
+        let rt = rrpc_runtime_new();
+
+        let rc = unsafe { rrpc_load_plugin_rt(rt, c"/nonexistent/librrpc_plugin.so".as_ptr(), c"geo".as_ptr()) };
+        assert_eq!(rc, ERR_PLUGIN);
+
+        let rc = unsafe { rrpc_unload_plugin_rt(rt, c"geo".as_ptr()) };
+        assert_eq!(rc, ERR_PLUGIN);
+        assert!(matches!(last_error(), Some(RpcError::NotFound(_))));
+
+        let rc = unsafe { rrpc_load_plugin_rt(rt, std::ptr::null(), c"geo".as_ptr()) };
+        assert_eq!(rc, ERR_PARSE_ERROR);
+
+        unsafe { rrpc_runtime_free(rt) };
+    }
 }
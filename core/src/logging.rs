@@ -0,0 +1,101 @@
+//! Forwarding of `log` records to the host
+//!
+//! Once the host installs a callback with `rrpc_set_log_callback`, this
+//! library registers itself as the process-wide `log` logger and passes
+//! every record at or above the current level to the callback. Handlers
+//! only need the `log` macros (`log::info!` and friends); the runtime logs
+//! through the same path.
+//!
+//! A record logged while the callback is running on the same thread is
+//! dropped rather than forwarded recursively.
+
+use crate::error::RpcError;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use parking_lot::RwLock;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// Receives a record's level, target and formatted message
+pub(crate) type Sink = Arc<dyn Fn(Level, &str, &str) + Send + Sync>;
+
+/// Filters in the order of their numeric codes (`LOG_OFF` .. `LOG_TRACE`)
+const FILTERS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+static SINK: RwLock<Option<Sink>> = RwLock::new(None);
+
+/// Index into `FILTERS` of the current level
+static LEVEL: AtomicUsize = AtomicUsize::new(3);
+
+/// Whether `HostLogger` became the `log` logger; another one may have won
+static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+static LOGGER: HostLogger = HostLogger;
+
+thread_local! {
+    static IN_SINK: Cell<bool> = const { Cell::new(false) };
+}
+
+struct HostLogger;
+
+impl Log for HostLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= level()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) || IN_SINK.with(Cell::get) {
+            return;
+        }
+        // Clone out of the lock so the callback may replace itself
+        let Some(sink) = SINK.read().clone() else {
+            return;
+        };
+
+        let message = record.args().to_string();
+        IN_SINK.with(|flag| flag.set(true));
+        sink(record.level(), record.target(), &message);
+        IN_SINK.with(|flag| flag.set(false));
+    }
+
+    fn flush(&self) {}
+}
+
+/// Current level filter
+pub(crate) fn level() -> LevelFilter {
+    FILTERS[LEVEL.load(Ordering::Relaxed)]
+}
+
+/// Set the level filter from its numeric code; fails for unknown codes
+pub(crate) fn set_level(code: usize) -> Result<(), RpcError> {
+    if code >= FILTERS.len() {
+        return Err(RpcError::Internal(format!("unknown log level {}", code)));
+    }
+    LEVEL.store(code, Ordering::Relaxed);
+    if SINK.read().is_some() {
+        log::set_max_level(FILTERS[code]);
+    }
+    Ok(())
+}
+
+/// Install or, with `None`, remove the sink
+///
+/// Fails if another `log` logger was installed in the process first.
+pub(crate) fn set_sink(sink: Option<Sink>) -> Result<(), RpcError> {
+    let installed = *INSTALLED.get_or_init(|| log::set_logger(&LOGGER).is_ok());
+    if !installed {
+        return Err(RpcError::Internal("another log logger is already installed".into()));
+    }
+
+    let enabled = sink.is_some();
+    *SINK.write() = sink;
+    log::set_max_level(if enabled { level() } else { LevelFilter::Off });
+    Ok(())
+}
@@ -0,0 +1,176 @@
+//! Dynamically loaded handler libraries
+//!
+//! A plugin is a cdylib that registers methods into a `Registry` handed to
+//! it by the host runtime. It declares its entry points with
+//! `export_plugin!`, which also exports the `PLUGIN_FINGERPRINT` of the
+//! `rrpc-core` it was built with, so mismatched builds are refused before
+//! any plugin code touches the registry.
+//!
+//! `Registry` is a Rust type without a stable layout, so a plugin must be
+//! built with the same compiler and the same `rrpc-core` version as the
+//! host library. The fingerprint is derived from both and from the layout
+//! of the types shared with plugins; nothing needs to be bumped by hand.
+//!
+//! Each handler loaded from a plugin keeps the library mapped; unloading
+//! removes the methods and the library is closed once the last snapshot
+//! holding them is dropped. Interceptors added by a plugin's registration
+//! function are ignored; only its methods are installed.
+//!
+//! # Example
+//! ```ignore
+//! // In the plugin crate (crate-type = ["cdylib"])
+//! fn register(registry: &mut rrpc_core::Registry) {
+//!     registry.register("add", |input| Ok(vec![input.iter().sum()]));
+//! }
+//!
+//! rrpc_core::export_plugin!(register);
+//! ```
+
+use crate::context::CallContext;
+use crate::error::RpcError;
+use crate::registry::{Handler, MethodHandler, Registry};
+use crate::stream::{StreamHandler, StreamSink};
+use libloading::Library;
+use std::ffi::OsStr;
+use std::os::raw::c_int;
+use std::sync::Arc;
+
+/// Fingerprint of this `rrpc-core` build, which a plugin must match
+///
+/// A hash of the crate version, the compiler version and the size and
+/// alignment of the types passed between host and plugin.
+pub const PLUGIN_FINGERPRINT: u64 = {
+    use std::mem::{align_of, size_of};
+    let mut hash = fnv1a(FNV_OFFSET, env!("CARGO_PKG_VERSION").as_bytes());
+    hash = fnv1a(hash, env!("RRPC_RUSTC_VERSION").as_bytes());
+    let layouts = [
+        size_of::<Registry>(),
+        align_of::<Registry>(),
+        size_of::<CallContext<'static>>(),
+        align_of::<CallContext<'static>>(),
+        size_of::<StreamSink<'static>>(),
+        align_of::<StreamSink<'static>>(),
+    ];
+    let mut i = 0;
+    while i < layouts.len() {
+        hash = fnv1a(hash, &(layouts[i] as u64).to_le_bytes());
+        i += 1;
+    }
+    hash
+};
+
+/// Symbol returning the plugin's `PLUGIN_FINGERPRINT`
+pub const FINGERPRINT_SYMBOL: &[u8] = b"rrpc_plugin_fingerprint\0";
+
+/// Symbol registering the plugin's methods; returns 0 on success
+pub const REGISTER_SYMBOL: &[u8] = b"rrpc_plugin_register\0";
+
+type FingerprintFn = unsafe extern "C" fn() -> u64;
+type RegisterFn = unsafe extern "C" fn(registry: *mut Registry) -> c_int;
+
+/// Export the plugin entry points for a `fn(&mut Registry)`
+///
+/// A panic in the registration function is caught and reported as a
+/// failed load.
+#[macro_export]
+macro_rules! export_plugin {
+    ($register:path) => {
+        #[no_mangle]
+        pub extern "C" fn rrpc_plugin_fingerprint() -> u64 {
+            $crate::plugin::PLUGIN_FINGERPRINT
+        }
+
+        /// # Safety
+        /// `registry` must point to a live `Registry` from the same `rrpc-core` build
+        #[no_mangle]
+        pub unsafe extern "C" fn rrpc_plugin_register(registry: *mut $crate::Registry) -> ::std::os::raw::c_int {
+            let register: fn(&mut $crate::Registry) = $register;
+            let registry = &mut *registry;
+            match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| register(registry))) {
+                Ok(()) => 0,
+                Err(_) => 1,
+            }
+        }
+    };
+}
+
+/// Open a plugin library and collect its methods into a fresh registry
+///
+/// # Safety
+/// Loading a library runs its initialisers; `path` must name a trusted plugin.
+pub(crate) unsafe fn load(path: &OsStr) -> Result<(Arc<Library>, Registry), RpcError> {
+    let failed = |what: &str| RpcError::Internal(format!("plugin {}: {}", path.to_string_lossy(), what));
+
+    let library = Library::new(path).map_err(|e| failed(&e.to_string()))?;
+
+    let fingerprint: FingerprintFn = *library
+        .get::<FingerprintFn>(FINGERPRINT_SYMBOL)
+        .map_err(|_| failed("missing rrpc_plugin_fingerprint (not built with this rrpc-core's export_plugin!)"))?;
+    let found = fingerprint();
+    if found != PLUGIN_FINGERPRINT {
+        return Err(failed(&format!(
+            "built with a different rrpc-core or compiler (fingerprint {:016x}, expected {:016x})",
+            found, PLUGIN_FINGERPRINT
+        )));
+    }
+
+    let register: RegisterFn = *library
+        .get::<RegisterFn>(REGISTER_SYMBOL)
+        .map_err(|_| failed("missing rrpc_plugin_register"))?;
+
+    let mut registry = Registry::new();
+    if register(&mut registry) != 0 {
+        return Err(failed("registration panicked"));
+    }
+
+    Ok((Arc::new(library), registry))
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a hash of `bytes`, continuing from `hash`
+const fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+    hash
+}
+
+/// Wrap a handler so it keeps `library` mapped for as long as it exists
+pub(crate) fn pin(handler: MethodHandler, library: &Arc<Library>) -> MethodHandler {
+    match handler {
+        MethodHandler::Unary(handler) => {
+            let pinned = Pinned { handler, _library: Arc::clone(library) };
+            let handler: Handler = Arc::new(move |ctx: &CallContext<'_>, input: &[u8]| pinned.unary(ctx, input));
+            MethodHandler::Unary(handler)
+        }
+        MethodHandler::Stream(handler) => {
+            let pinned = Pinned { handler, _library: Arc::clone(library) };
+            let handler: StreamHandler = Arc::new(
+                move |ctx: &CallContext<'_>, input: &[u8], sink: &mut StreamSink<'_>| pinned.stream(ctx, input, sink),
+            );
+            MethodHandler::Stream(handler)
+        }
+    }
+}
+
+/// A plugin handler and its library; fields drop in order, code before library
+struct Pinned<H> {
+    handler: H,
+    _library: Arc<Library>,
+}
+
+impl Pinned<Handler> {
+    fn unary(&self, ctx: &CallContext<'_>, input: &[u8]) -> Result<Vec<u8>, RpcError> {
+        (self.handler)(ctx, input)
+    }
+}
+
+impl Pinned<StreamHandler> {
+    fn stream(&self, ctx: &CallContext<'_>, input: &[u8], sink: &mut StreamSink<'_>) -> Result<(), RpcError> {
+        (self.handler)(ctx, input, sink)
+    }
+}
@@ -0,0 +1,184 @@
+//! Worker pools used for asynchronous and timed calls
+
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A pool of worker threads pulling jobs from a shared queue
+pub(crate) struct WorkerPool {
+    sender: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    /// Spawn a pool with one worker per available CPU
+    pub(crate) fn new() -> Self {
+        let size = thread::available_parallelism().map_or(4, |n| n.get());
+        Self::with_threads(size)
+    }
+
+    /// Spawn a pool with `size` workers (at least one)
+    pub(crate) fn with_threads(size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..size.max(1))
+            .map(|i| {
+                let receiver = Arc::clone(&receiver);
+                thread::Builder::new()
+                    .name(format!("rrpc-worker-{}", i))
+                    .spawn(move || worker_loop(&receiver))
+                    .expect("failed to spawn rrpc worker thread")
+            })
+            .collect();
+
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    /// Queue a job for execution on a worker thread
+    pub(crate) fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        if let Some(sender) = &self.sender {
+            // Workers only exit once the sender is dropped, so this cannot fail
+            let _ = sender.send(Box::new(job));
+        }
+    }
+}
+
+fn worker_loop(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        // Hold the lock only while waiting for the next job
+        let job = receiver.lock().recv();
+        match job {
+            Ok(job) => job(),
+            Err(_) => break,
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    /// Finish queued jobs and join all workers
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A pool that starts workers on demand, up to a cap, and reuses idle ones
+///
+/// Unlike `WorkerPool` it never joins its workers: a job that never returns
+/// keeps its thread busy for good but is still counted against the cap, so
+/// stuck jobs cannot pile up threads without bound. Jobs beyond the cap wait
+/// in the queue for a free worker.
+pub(crate) struct BoundedPool {
+    name: &'static str,
+    max_threads: usize,
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<BoundedState>,
+    ready: Condvar,
+}
+
+#[derive(Default)]
+struct BoundedState {
+    jobs: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+    closed: bool,
+}
+
+impl BoundedPool {
+    /// Create a pool of at most `max_threads` workers (at least one); none are started yet
+    pub(crate) fn new(name: &'static str, max_threads: usize) -> Self {
+        Self {
+            name,
+            max_threads: max_threads.max(1),
+            shared: Arc::default(),
+        }
+    }
+
+    /// Queue a job, starting a worker if none is idle and the cap allows
+    pub(crate) fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        let mut state = self.shared.state.lock();
+        state.jobs.push_back(Box::new(job));
+        if state.idle >= state.jobs.len() {
+            self.shared.ready.notify_one();
+            return;
+        }
+        if state.threads < self.max_threads {
+            let shared = Arc::clone(&self.shared);
+            let spawned = thread::Builder::new()
+                .name(self.name.into())
+                .spawn(move || bounded_worker_loop(&shared));
+            // Without a new thread the job waits for a busy one
+            if spawned.is_ok() {
+                state.threads += 1;
+            }
+        }
+    }
+
+    /// Number of workers started so far, busy or idle
+    #[cfg(test)]
+    pub(crate) fn threads(&self) -> usize {
+        self.shared.state.lock().threads
+    }
+}
+
+fn bounded_worker_loop(shared: &Shared) {
+    let mut state = shared.state.lock();
+    loop {
+        if let Some(job) = state.jobs.pop_front() {
+            drop(state);
+            job();
+            state = shared.state.lock();
+        } else if state.closed {
+            state.threads -= 1;
+            return;
+        } else {
+            state.idle += 1;
+            shared.ready.wait(&mut state);
+            state.idle -= 1;
+        }
+    }
+}
+
+impl Drop for BoundedPool {
+    /// Let workers exit once the queue is empty, without waiting for them
+    fn drop(&mut self) {
+        self.shared.state.lock().closed = true;
+        self.shared.ready.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_runs_all_jobs_before_drop() {
+        let done = Arc::new(AtomicUsize::new(0));
+        let pool = WorkerPool::with_threads(3);
+
+        for _ in 0..20 {
+            let done = Arc::clone(&done);
+            pool.execute(move || {
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        drop(pool);
+
+        assert_eq!(done.load(Ordering::SeqCst), 20);
+    }
+}
@@ -0,0 +1,100 @@
+//! Methods registered at link time
+//!
+//! `#[rrpc_core::method]` submits a `MethodRegistration` for each annotated
+//! function to a collection built by the linker. `rrpc_init` installs all of
+//! them into the default runtime; Rust code can do the same for any
+//! registry with `Registry::register_collected`.
+//!
+//! Only functions in crates that are actually linked into the final binary
+//! are collected.
+
+use crate::registry::Registry;
+
+/// A method submitted by `#[rrpc_core::method]`
+pub struct MethodRegistration {
+    name: &'static str,
+    register: fn(&mut Registry),
+}
+
+impl MethodRegistration {
+    /// Registration for `name`, installed by `register`
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, register: fn(&mut Registry)) -> Self {
+        Self { name, register }
+    }
+
+    /// Method name
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+inventory::collect!(MethodRegistration);
+
+/// Every collected registration, sorted by method name
+pub fn collected() -> Vec<&'static MethodRegistration> {
+    let mut all: Vec<_> = inventory::iter::<MethodRegistration>.into_iter().collect();
+    all.sort_by_key(|registration| registration.name);
+    all
+}
+
+impl Registry {
+    /// Register every method declared with `#[rrpc_core::method]`
+    ///
+    /// Existing methods with the same names are replaced.
+    pub fn register_collected(&mut self) {
+        for registration in collected() {
+            (registration.register)(self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Registry, RpcError};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Deserialize)]
+    struct Scale {
+        value: i64,
+        by: i64,
+    }
+
+    #[derive(Debug, PartialEq, Serialize)]
+    struct Scaled {
+        value: i64,
+    }
+
+    /// Multiply a value
+    #[crate::method("test.collect.scale")]
+    fn scale(req: Scale) -> Result<Scaled, RpcError> {
+        Ok(Scaled { value: req.value * req.by })
+    }
+
+    #[crate::method(requires = "test.collect.raw", audited)]
+    fn collect_raw(input: &[u8]) -> Result<Vec<u8>, RpcError> {
+        Ok(input.iter().rev().copied().collect())
+    }
+
+    #[test]
+    fn test_register_collected_installs_annotated_functions() {
+        let mut registry = Registry::new();
+        registry.register_collected();
+
+        assert_eq!(
+            registry.call("test.collect.scale", br#"{"value":6,"by":7}"#).unwrap(),
+            br#"{"value":42}"#
+        );
+        assert!(matches!(registry.call("collect_raw", b"abc"), Err(RpcError::PermissionDenied(_))));
+        registry.grant_default("test.collect.raw");
+        assert_eq!(registry.call("collect_raw", b"abc").unwrap(), b"cba");
+
+        let info = registry.describe("test.collect.scale").unwrap();
+        assert_eq!(info.description.as_deref(), Some("Multiply a value"));
+        assert!(info.input_type.unwrap().ends_with("Scale"));
+        assert!(registry.describe("collect_raw").unwrap().audited);
+
+        // The annotated function is still an ordinary function
+        assert_eq!(scale(Scale { value: 2, by: 3 }).unwrap(), Scaled { value: 6 });
+    }
+}
@@ -1,14 +1,153 @@
 //! Function registry for RPC handlers
 
+use crate::batch::{self, BatchMode};
+use crate::codec::Codec;
+use crate::context::{CallContext, CallOptions};
 use crate::error::RpcError;
+use crate::executor::block_on;
+use crate::audit::{self, AuditRecord, AuditSink};
+use crate::capability::CapabilityToken;
+use crate::interceptor::{Layer, Next, Scope, Terminal};
+use crate::introspect::{Metadata, MethodInfo};
+use crate::stats::{Collector, MethodStats};
+use crate::stream::{StreamHandler, StreamSink};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::{self, Any};
 use std::collections::HashMap;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-/// Handler function type: input bytes → Result<output bytes, error>
-pub type Handler = Box<dyn Fn(&[u8]) -> Result<Vec<u8>, RpcError> + Send + Sync>;
+/// Handler function type: (call context, input bytes) → Result<output bytes, error>
+///
+/// Handlers are reference-counted so registries can be cloned cheaply.
+pub type Handler = Arc<dyn Fn(&CallContext<'_>, &[u8]) -> Result<Vec<u8>, RpcError> + Send + Sync>;
+
+/// A registered method and its settings
+#[derive(Clone)]
+struct Entry {
+    handler: MethodHandler,
+    timeout: Option<Duration>,
+    requires: Vec<String>,
+    audited: bool,
+    meta: Metadata,
+}
+
+impl Entry {
+    fn info(&self, name: &str, requires: Vec<String>, audited: bool) -> MethodInfo {
+        MethodInfo {
+            name: name.to_string(),
+            streaming: matches!(self.handler, MethodHandler::Stream(_)),
+            description: self.meta.description.clone(),
+            input_type: self.meta.input_type.clone(),
+            output_type: self.meta.output_type.clone(),
+            deprecated: self.meta.deprecated,
+            timeout: self.timeout,
+            requires,
+            audited,
+        }
+    }
+}
+
+/// Handler of a registered method, by calling convention
+#[derive(Clone)]
+pub enum MethodHandler {
+    /// Returns one output buffer
+    Unary(Handler),
+    /// Pushes output frames into a `StreamSink`
+    Stream(StreamHandler),
+}
+
+/// Settings of a registered method, returned by the `register*` functions
+///
+/// # Example
+/// ```
+/// use rrpc_core::Registry;
+/// use std::time::Duration;
+///
+/// let mut registry = Registry::new();
+/// registry
+///     .register("layout", |input| Ok(input.to_vec()))
+///     .timeout(Duration::from_secs(2))
+///     .description("Lay out the graph")
+///     .input_type("LayoutRequest")
+///     .output_type("Positions");
+/// ```
+pub struct MethodConfig<'a> {
+    entry: &'a mut Entry,
+}
+
+impl MethodConfig<'_> {
+    /// Default timeout for every call of this method
+    ///
+    /// A caller-supplied deadline that is earlier still wins.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.entry.timeout = Some(timeout);
+        self
+    }
+
+    /// Human-readable description reported by introspection
+    pub fn description(self, description: impl Into<String>) -> Self {
+        self.entry.meta.description = Some(description.into());
+        self
+    }
+
+    /// Name of the input type reported by introspection
+    pub fn input_type(self, name: impl Into<String>) -> Self {
+        self.entry.meta.input_type = Some(name.into());
+        self
+    }
+
+    /// Name of the output type reported by introspection
+    pub fn output_type(self, name: impl Into<String>) -> Self {
+        self.entry.meta.output_type = Some(name.into());
+        self
+    }
+
+    /// Require callers to hold `capability`; may be given several times
+    ///
+    /// See the `capability` module.
+    pub fn requires(self, capability: impl Into<String>) -> Self {
+        let capability = capability.into();
+        if !self.entry.requires.contains(&capability) {
+            self.entry.requires.push(capability);
+        }
+        self
+    }
+
+    /// Record every call in the audit log; see the `audit` module
+    pub fn audited(self) -> Self {
+        self.entry.audited = true;
+        self
+    }
+
+    /// Mark the method as deprecated; calls still work
+    pub fn deprecated(self) -> Self {
+        self.entry.meta.deprecated = true;
+        self
+    }
+}
+
+/// Default limit for nested `CallContext::call` invocations
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 32;
 
 /// Registry of RPC method handlers
+///
+/// Cloning a registry shares the handlers, the default capability grants,
+/// the audit sink and the call statistics; it is how `Runtime` publishes
+/// immutable snapshots.
+#[derive(Clone)]
 pub struct Registry {
-    handlers: HashMap<String, Handler>,
+    handlers: HashMap<String, Entry>,
+    interceptors: Vec<Layer>,
+    rules: Vec<(Scope, String)>,
+    grants: CapabilityToken,
+    audit_rules: Vec<Scope>,
+    audit_sink: Option<Arc<dyn AuditSink>>,
+    stats: Arc<Collector>,
+    max_depth: usize,
 }
 
 impl Registry {
@@ -16,6 +155,13 @@ impl Registry {
     pub fn new() -> Self {
         Self {
             handlers: HashMap::new(),
+            interceptors: Vec::new(),
+            rules: Vec::new(),
+            grants: CapabilityToken::new(),
+            audit_rules: Vec::new(),
+            audit_sink: None,
+            stats: Arc::default(),
+            max_depth: DEFAULT_MAX_CALL_DEPTH,
         }
     }
 
@@ -28,21 +174,539 @@ impl Registry {
     /// let mut registry = Registry::new();
     /// registry.register("echo", |input| Ok(input.to_vec()));
     /// ```
-    pub fn register<F>(&mut self, name: impl Into<String>, handler: F)
+    pub fn register<F>(&mut self, name: impl Into<String>, handler: F) -> MethodConfig<'_>
     where
         F: Fn(&[u8]) -> Result<Vec<u8>, RpcError> + Send + Sync + 'static,
     {
-        self.handlers.insert(name.into(), Box::new(handler));
+        self.register_with_ctx(name, move |_, input| handler(input))
+    }
+
+    /// Register a handler that receives the call context
+    ///
+    /// The context lets a handler invoke other methods of this registry.
+    ///
+    /// # Example
+    /// ```
+    /// use rrpc_core::{Registry, RpcError};
+    ///
+    /// let mut registry = Registry::new();
+    /// registry.register("double", |input| Ok([input, input].concat()));
+    /// registry.register_with_ctx("quadruple", |ctx, input| {
+    ///     let twice = ctx.call("double", input)?;
+    ///     ctx.call("double", &twice)
+    /// });
+    ///
+    /// assert_eq!(registry.call("quadruple", b"a").unwrap(), b"aaaa");
+    /// ```
+    pub fn register_with_ctx<F>(&mut self, name: impl Into<String>, handler: F) -> MethodConfig<'_>
+    where
+        F: Fn(&CallContext<'_>, &[u8]) -> Result<Vec<u8>, RpcError> + Send + Sync + 'static,
+    {
+        self.insert(name.into(), MethodHandler::Unary(Arc::new(handler)))
+    }
+
+    /// Register a handler taking and returning serde types
+    ///
+    /// The request is decoded and the response encoded with codec `C`.
+    /// Decode failures are returned as `RpcError::ParseError` and encode
+    /// failures as `RpcError::SerializationError`. The type names are
+    /// recorded as the method's input and output types.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "json")] {
+    /// use rrpc_core::codec::Json;
+    /// use rrpc_core::{Registry, RpcError};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Add { a: i64, b: i64 }
+    ///
+    /// #[derive(Serialize)]
+    /// struct Sum { sum: i64 }
+    ///
+    /// let mut registry = Registry::new();
+    /// registry.register_typed::<Add, Sum, Json>("add", |req| Ok(Sum { sum: req.a + req.b }));
+    ///
+    /// assert_eq!(registry.call("add", br#"{"a":2,"b":3}"#).unwrap(), br#"{"sum":5}"#);
+    /// assert!(matches!(registry.call("add", b"nope"), Err(RpcError::ParseError(_))));
+    /// # }
+    /// ```
+    pub fn register_typed<Req, Resp, C>(
+        &mut self,
+        name: impl Into<String>,
+        handler: impl Fn(Req) -> Result<Resp, RpcError> + Send + Sync + 'static,
+    ) -> MethodConfig<'_>
+    where
+        Req: DeserializeOwned,
+        Resp: Serialize,
+        C: Codec,
+    {
+        self.register(name, move |input| C::encode(&handler(C::decode(input)?)?))
+            .input_type(any::type_name::<Req>())
+            .output_type(any::type_name::<Resp>())
+    }
+
+    /// Register a server-streaming handler
+    ///
+    /// The handler pushes frames into the sink; use `call_stream` (or
+    /// `rrpc_call_stream` from the host) to consume them. `call` on a
+    /// streaming method fails.
+    ///
+    /// # Example
+    /// ```
+    /// use rrpc_core::Registry;
+    ///
+    /// let mut registry = Registry::new();
+    /// registry.register_stream("count", |_ctx, _input, sink| {
+    ///     for i in 0u8..3 {
+    ///         sink.send(&[i])?;
+    ///     }
+    ///     Ok(())
+    /// });
+    ///
+    /// let mut frames = Vec::new();
+    /// registry.call_stream("count", b"", |frame| { frames.push(frame[0]); true }).unwrap();
+    /// assert_eq!(frames, [0, 1, 2]);
+    /// ```
+    pub fn register_stream<F>(&mut self, name: impl Into<String>, handler: F) -> MethodConfig<'_>
+    where
+        F: Fn(&CallContext<'_>, &[u8], &mut StreamSink<'_>) -> Result<(), RpcError> + Send + Sync + 'static,
+    {
+        self.insert(name.into(), MethodHandler::Stream(Arc::new(handler)))
+    }
+
+    /// Register a handler that returns a future
+    ///
+    /// The handler receives an owned copy of the input so the future can be
+    /// `'static`. Each call drives the future to completion on the thread
+    /// performing the call; use `rrpc_call_async` / `Runtime::call_async` to
+    /// keep the caller's thread free.
+    ///
+    /// # Example
+    /// ```
+    /// use rrpc_core::{Registry, RpcError};
+    ///
+    /// let mut registry = Registry::new();
+    /// registry.register_async("echo", |input: Vec<u8>| async move { Ok::<_, RpcError>(input) });
+    ///
+    /// assert_eq!(registry.call("echo", b"hi").unwrap(), b"hi");
+    /// ```
+    pub fn register_async<F, Fut>(&mut self, name: impl Into<String>, handler: F) -> MethodConfig<'_>
+    where
+        F: Fn(Vec<u8>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<u8>, RpcError>> + Send + 'static,
+    {
+        self.register(name, move |input| block_on(handler(input.to_vec())))
+    }
+
+    /// Change the settings of an already registered method
+    pub fn configure(&mut self, method: &str) -> Option<MethodConfig<'_>> {
+        self.handlers
+            .get_mut(method)
+            .map(|entry| MethodConfig { entry })
+    }
+
+    /// Remove a method, returning its handler
+    ///
+    /// Snapshots that already hold the method keep it until they are dropped.
+    pub fn unregister(&mut self, method: &str) -> Option<MethodHandler> {
+        self.handlers.remove(method).map(|entry| entry.handler)
+    }
+
+    /// Swap a method's handler, returning the previous one
+    ///
+    /// Unlike `register*`, an existing method keeps its timeout, required
+    /// capabilities, audit flag and metadata. A new method is added with default
+    /// settings.
+    ///
+    /// # Example
+    /// ```
+    /// use rrpc_core::registry::MethodHandler;
+    /// use rrpc_core::Registry;
+    /// use std::sync::Arc;
+    ///
+    /// let mut registry = Registry::new();
+    /// registry.register("version", |_| Ok(b"v1".to_vec()));
+    ///
+    /// let previous = registry.replace("version", MethodHandler::Unary(Arc::new(|_ctx, _input| Ok(b"v2".to_vec()))));
+    /// assert!(matches!(previous, Some(MethodHandler::Unary(_))));
+    /// assert_eq!(registry.call("version", b"").unwrap(), b"v2");
+    /// ```
+    pub fn replace(&mut self, name: impl Into<String>, handler: MethodHandler) -> Option<MethodHandler> {
+        let name = name.into();
+        match self.handlers.get_mut(&name) {
+            Some(entry) => Some(std::mem::replace(&mut entry.handler, handler)),
+            None => {
+                self.insert(name, handler);
+                None
+            }
+        }
+    }
+
+    /// Move every method of `other` into this registry as `prefix.name`
+    ///
+    /// Each handler is passed through `wrap` on the way. Fails without
+    /// changing anything if one of the resulting names is already taken.
+    pub(crate) fn absorb(
+        &mut self,
+        other: Registry,
+        prefix: &str,
+        wrap: impl Fn(MethodHandler) -> MethodHandler,
+    ) -> Result<Vec<String>, RpcError> {
+        let qualify = |name: &str| match prefix {
+            "" => name.to_string(),
+            prefix => format!("{}.{}", prefix, name),
+        };
+
+        if let Some(taken) = other.handlers.keys().map(|n| qualify(n)).find(|n| self.handlers.contains_key(n)) {
+            return Err(RpcError::Internal(format!("method '{}' is already registered", taken)));
+        }
+
+        let mut names = Vec::with_capacity(other.handlers.len());
+        for (name, mut entry) in other.handlers {
+            let name = qualify(&name);
+            entry.handler = wrap(entry.handler);
+            names.push(name.clone());
+            self.handlers.insert(name, entry);
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Insert or replace a method with default settings
+    fn insert(&mut self, name: String, handler: MethodHandler) -> MethodConfig<'_> {
+        let entry = Entry {
+            handler,
+            timeout: None,
+            requires: Vec::new(),
+            audited: false,
+            meta: Metadata::default(),
+        };
+        let entry = match self.handlers.entry(name) {
+            std::collections::hash_map::Entry::Occupied(mut slot) => {
+                slot.insert(entry);
+                slot.into_mut()
+            }
+            std::collections::hash_map::Entry::Vacant(slot) => slot.insert(entry),
+        };
+        MethodConfig { entry }
+    }
+
+    /// Add an interceptor around the calls of the methods matching `pattern`
+    ///
+    /// `pattern` is `"*"` for every method, a prefix ending in `*` such as
+    /// `"udg.*"`, or an exact method name. Interceptors run in the order
+    /// they were added, the first being the outermost, and apply to nested
+    /// calls made through `CallContext::call` too.
+    ///
+    /// Around a streaming method an interceptor sees an empty output on
+    /// success, and the output it returns is discarded; it can still
+    /// reject the call, rewrite the input or time it.
+    pub fn intercept<F>(&mut self, pattern: &str, interceptor: F)
+    where
+        F: Fn(&CallContext<'_>, &[u8], Next<'_>) -> Result<Vec<u8>, RpcError> + Send + Sync + 'static,
+    {
+        self.interceptors.push(Layer::new(pattern, Arc::new(interceptor)));
+    }
+
+    /// Require `capability` for every method matching `pattern`
+    ///
+    /// `pattern` is `"*"`, a prefix ending in `*` such as `"plugin.*"`, or
+    /// an exact method name, and also covers methods registered later.
+    /// Use it to restrict methods that do not declare requirements
+    /// themselves, such as those of a plugin or the host.
+    pub fn require(&mut self, pattern: &str, capability: impl Into<String>) {
+        self.rules.push((Scope::parse(pattern), capability.into()));
+    }
+
+    /// Grant `capability` to every call made without a `CapabilityToken`
+    ///
+    /// This is the registry's (and so the runtime's) default policy;
+    /// initially nothing is granted. Takes effect immediately for every
+    /// clone of the registry.
+    pub fn grant_default(&self, capability: &str) {
+        self.grants.grant(capability);
+    }
+
+    /// Audit every method matching `pattern`
+    ///
+    /// Accepts the same patterns as `require`, and also covers methods
+    /// registered later. Use it for methods that are not flagged with
+    /// `MethodConfig::audited` themselves, such as those of a plugin.
+    pub fn audit(&mut self, pattern: &str) {
+        self.audit_rules.push(Scope::parse(pattern));
+    }
+
+    /// Send audit records to `sink`, or with `None` stop recording
+    pub fn set_audit_sink(&mut self, sink: Option<Arc<dyn AuditSink>>) {
+        self.audit_sink = sink;
+    }
+
+    /// Set the maximum nesting depth for calls made through `CallContext::call`
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
     }
 
     /// Call a registered method
+    ///
+    /// A panicking handler does not unwind into the caller; the panic is
+    /// caught and returned as `RpcError::Panic` with the panic message.
     pub fn call(&self, method: &str, input: &[u8]) -> Result<Vec<u8>, RpcError> {
-        let handler = self
+        self.call_with(method, input, &CallOptions::default())
+    }
+
+    /// Call a registered method with per-call options
+    ///
+    /// A call whose cancellation token is triggered before the handler
+    /// starts or before it returns fails with `RpcError::Cancelled`; one
+    /// that finishes after its deadline fails with `RpcError::Timeout`.
+    /// Deadlines are cooperative here: the handler runs on the calling
+    /// thread and must watch `CallContext::deadline` itself. `Runtime`
+    /// calls enforce them without waiting for the handler.
+    pub fn call_with(&self, method: &str, input: &[u8], options: &CallOptions) -> Result<Vec<u8>, RpcError> {
+        self.dispatch(method, input, 0, options)
+    }
+
+    /// Call several methods in one go, returning one result per entry
+    ///
+    /// Results are in entry order. Entries skipped because of
+    /// `BatchMode::stop_on_error` fail with `RpcError::Cancelled`.
+    pub fn call_batch(&self, calls: &[(&str, &[u8])], mode: BatchMode) -> Vec<Result<Vec<u8>, RpcError>> {
+        batch::run(calls.len(), mode, |i| self.call(calls[i].0, calls[i].1))
+            .into_iter()
+            .map(|result| result.unwrap_or(Err(RpcError::Cancelled)))
+            .collect()
+    }
+
+    /// Call a method and forward each output frame to `on_item`
+    ///
+    /// `on_item` returns `false` to stop the stream early, which is not an
+    /// error. Unary methods are delivered as a single frame.
+    pub fn call_stream<F>(&self, method: &str, input: &[u8], on_item: F) -> Result<(), RpcError>
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        self.call_stream_with(method, input, &CallOptions::default(), on_item)
+    }
+
+    /// Stream a method's output frames with per-call options
+    pub fn call_stream_with<F>(
+        &self,
+        method: &str,
+        input: &[u8],
+        options: &CallOptions,
+        mut on_item: F,
+    ) -> Result<(), RpcError>
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        let entry = self
+            .lookup(method, 0, options)
+            .inspect_err(|e| self.rejected(method, input, options, e))?;
+        let scoped;
+        let options = match entry.timeout {
+            Some(timeout) => {
+                scoped = options.clone().with_timeout(timeout);
+                &scoped
+            }
+            None => options,
+        };
+        let ctx = CallContext::new(self, method, 0, options);
+
+        let started = Instant::now();
+        let mut bytes_out = 0;
+        let mut on_item = |frame: &[u8]| {
+            bytes_out += frame.len();
+            on_item(frame)
+        };
+        let result = match &entry.handler {
+            MethodHandler::Unary(handler) => guarded(|| self.intercepted(&ctx, input, &mut |input| handler(&ctx, input)))
+                .and_then(|output| {
+                    on_item(&output);
+                    options.check()
+                }),
+            MethodHandler::Stream(handler) => {
+                let mut sink = StreamSink::new(&mut on_item, options);
+                let result = guarded(|| {
+                    self.intercepted(&ctx, input, &mut |input| handler(&ctx, input, &mut sink).map(|()| Vec::new()))
+                });
+                if sink.is_closed() {
+                    Ok(())
+                } else {
+                    options.check().and(result.map(drop))
+                }
+            }
+        };
+        self.observe(method, entry, input, options, result.as_ref().map(|()| bytes_out), started);
+        result
+    }
+
+    /// Invoke a unary handler at the given nesting depth
+    pub(crate) fn dispatch(
+        &self,
+        method: &str,
+        input: &[u8],
+        depth: usize,
+        options: &CallOptions,
+    ) -> Result<Vec<u8>, RpcError> {
+        let entry = self
+            .lookup(method, depth, options)
+            .inspect_err(|e| self.rejected(method, input, options, e))?;
+        let MethodHandler::Unary(handler) = &entry.handler else {
+            return Err(RpcError::Internal(format!(
+                "'{}' is a streaming method; use call_stream",
+                method
+            )));
+        };
+
+        let scoped;
+        let options = match entry.timeout {
+            Some(timeout) => {
+                scoped = options.clone().with_timeout(timeout);
+                &scoped
+            }
+            None => options,
+        };
+
+        let started = Instant::now();
+        let ctx = CallContext::new(self, method, depth, options);
+        let result = guarded(|| self.intercepted(&ctx, input, &mut |input| handler(&ctx, input)));
+        let result = options.check().and(result);
+        self.observe(method, entry, input, options, result.as_ref().map(Vec::len), started);
+        result
+    }
+
+    /// Count a finished call, audit it and log handler panics
+    fn observe(
+        &self,
+        method: &str,
+        entry: &Entry,
+        input: &[u8],
+        options: &CallOptions,
+        outcome: Result<usize, &RpcError>,
+        started: Instant,
+    ) {
+        let elapsed = started.elapsed();
+        self.stats.record(method, input.len(), outcome, elapsed);
+        self.record_audit(method, entry, input, options, outcome.map(drop), elapsed);
+        if let Err(RpcError::Panic(message)) = outcome {
+            log::error!("handler for '{}' panicked: {}", method, message);
+        }
+    }
+
+    /// Count and audit a call of a registered method that was refused before it ran
+    fn rejected(&self, method: &str, input: &[u8], options: &CallOptions, error: &RpcError) {
+        if let Some(entry) = self.handlers.get(method) {
+            self.stats.record(method, input.len(), Err(error), Duration::ZERO);
+            self.record_audit(method, entry, input, options, Err(error), Duration::ZERO);
+        }
+    }
+
+    /// Send a record to the audit sink if the method is audited
+    fn record_audit(
+        &self,
+        method: &str,
+        entry: &Entry,
+        input: &[u8],
+        options: &CallOptions,
+        outcome: Result<(), &RpcError>,
+        duration: Duration,
+    ) {
+        let Some(sink) = &self.audit_sink else {
+            return;
+        };
+        if !self.is_audited(method, entry) {
+            return;
+        }
+
+        let record = AuditRecord {
+            timestamp: SystemTime::now(),
+            method: method.to_string(),
+            caller: options
+                .capabilities
+                .as_ref()
+                .and_then(CapabilityToken::identity)
+                .map(str::to_string),
+            capabilities: self.info(method, entry).requires,
+            input_sha256: audit::sha256(input),
+            code: outcome.map_or_else(crate::error_code, |()| crate::ERR_SUCCESS),
+            duration,
+        };
+        if let Err(e) = sink.record(&record) {
+            log::error!("failed to audit call of '{}': {}", method, e);
+        }
+    }
+
+    fn is_audited(&self, method: &str, entry: &Entry) -> bool {
+        entry.audited || self.audit_rules.iter().any(|scope| scope.matches(method))
+    }
+
+    /// Run a call through the interceptors that apply to it
+    fn intercepted(
+        &self,
+        ctx: &CallContext<'_>,
+        input: &[u8],
+        handler: &mut Terminal<'_>,
+    ) -> Result<Vec<u8>, RpcError> {
+        Next::new(&self.interceptors, ctx, handler).run(input)
+    }
+
+    /// Effective deadline of a call: the caller's or the method's timeout
+    pub(crate) fn deadline_for(&self, method: &str, options: &CallOptions) -> Option<Instant> {
+        let timeout = self.handlers.get(method).and_then(|entry| entry.timeout);
+        match timeout {
+            Some(timeout) => options.clone().with_timeout(timeout).deadline,
+            None => options.deadline,
+        }
+    }
+
+    /// Find a method, enforcing the call depth limit, cancellation, deadline
+    /// and required capabilities
+    fn lookup(&self, method: &str, depth: usize, options: &CallOptions) -> Result<&Entry, RpcError> {
+        options.check()?;
+
+        if depth > self.max_depth {
+            return Err(RpcError::Internal(format!(
+                "call depth limit of {} exceeded calling '{}'",
+                self.max_depth, method
+            )));
+        }
+
+        let entry = self
             .handlers
             .get(method)
             .ok_or_else(|| RpcError::UnknownMethod(method.to_string()))?;
 
-        handler(input)
+        let token = options.capabilities.as_ref().unwrap_or(&self.grants);
+        if let Some(missing) = self.required(method, entry).find(|capability| !token.allows(capability)) {
+            return Err(RpcError::PermissionDenied(format!(
+                "'{}' requires capability '{}'",
+                method, missing
+            )));
+        }
+        Ok(entry)
+    }
+
+    /// Capabilities needed to call a method: its own and those of matching rules
+    fn required<'a>(&'a self, method: &'a str, entry: &'a Entry) -> impl Iterator<Item = &'a str> + 'a {
+        let rules = self
+            .rules
+            .iter()
+            .filter(move |(scope, _)| scope.matches(method))
+            .map(|(_, capability)| capability.as_str());
+        entry.requires.iter().map(String::as_str).chain(rules)
+    }
+
+    /// Describe a method, including the capabilities imposed by rules
+    fn info(&self, method: &str, entry: &Entry) -> MethodInfo {
+        let mut requires: Vec<String> = Vec::new();
+        for capability in self.required(method, entry) {
+            if !requires.iter().any(|c| c == capability) {
+                requires.push(capability.to_string());
+            }
+        }
+        entry.info(method, requires, self.is_audited(method, entry))
     }
 
     /// Check if a method is registered
@@ -54,6 +718,47 @@ impl Registry {
     pub fn methods(&self) -> Vec<&str> {
         self.handlers.keys().map(|s| s.as_str()).collect()
     }
+
+    /// Describe a registered method
+    pub fn describe(&self, method: &str) -> Option<MethodInfo> {
+        self.handlers.get(method).map(|entry| self.info(method, entry))
+    }
+
+    /// Call statistics of every method called so far, sorted by name
+    ///
+    /// See the `stats` module for what is counted.
+    pub fn stats(&self) -> Vec<MethodStats> {
+        self.stats.snapshot()
+    }
+
+    /// Reset the call statistics of every method
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
+    /// Describe all registered methods, sorted by name
+    pub fn describe_all(&self) -> Vec<MethodInfo> {
+        let mut infos: Vec<_> = self.handlers.iter().map(|(name, entry)| self.info(name, entry)).collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+}
+
+/// Run a handler, converting a panic into `RpcError::Panic`
+fn guarded<T>(f: impl FnOnce() -> Result<T, RpcError>) -> Result<T, RpcError> {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(RpcError::Panic(panic_message(payload))))
+}
+
+/// Extract a readable message from a panic payload
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
 }
 
 impl Default for Registry {
@@ -93,4 +798,248 @@ mod tests {
         assert!(registry.has_method("test"));
         assert!(!registry.has_method("missing"));
     }
+
+    #[test]
+    fn test_panic_is_caught() {
+        let mut registry = Registry::new();
+
+        registry.register("boom", |_| panic!("kaboom"));
+        registry.register("echo", |input| Ok(input.to_vec()));
+
+        let result = registry.call("boom", b"");
+        assert!(matches!(result, Err(RpcError::Panic(ref m)) if m == "kaboom"));
+
+        // Registry is still usable after a panic
+        assert_eq!(registry.call("echo", b"ok").unwrap(), b"ok");
+    }
+
+    #[test]
+    fn test_nested_call() {
+        let mut registry = Registry::new();
+
+        registry.register("upper", |input| Ok(input.to_ascii_uppercase()));
+        registry.register_with_ctx("shout", |ctx, input| {
+            assert_eq!(ctx.method(), "shout");
+            let mut out = ctx.call("upper", input)?;
+            out.push(b'!');
+            Ok(out)
+        });
+
+        assert_eq!(registry.call("shout", b"hi").unwrap(), b"HI!");
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        let mut registry = Registry::new();
+        registry.set_max_call_depth(4);
+
+        registry.register_with_ctx("recurse", |ctx, input| {
+            assert!(ctx.depth() <= 4);
+            ctx.call("recurse", input)
+        });
+
+        let result = registry.call("recurse", b"");
+        assert!(matches!(result, Err(RpcError::Internal(ref m)) if m.contains("depth limit")));
+    }
+
+    #[test]
+    fn test_stream_frames_and_errors() {
+        let mut registry = Registry::new();
+
+        registry.register_stream("numbers", |_, input, sink| {
+            for i in 0..input[0] {
+                sink.send(&[i])?;
+            }
+            if input[0] == 0 {
+                return Err(RpcError::NotFound("nothing to stream".into()));
+            }
+            Ok(())
+        });
+        registry.register("single", |_| Ok(b"one".to_vec()));
+
+        let mut frames = Vec::new();
+        registry.call_stream("numbers", &[4], |f| { frames.push(f.to_vec()); true }).unwrap();
+        assert_eq!(frames, [[0], [1], [2], [3]]);
+
+        let result = registry.call_stream("numbers", &[0], |_| true);
+        assert!(matches!(result, Err(RpcError::NotFound(_))));
+
+        let mut frames = Vec::new();
+        registry.call_stream("single", b"", |f| { frames.push(f.to_vec()); true }).unwrap();
+        assert_eq!(frames, [b"one".to_vec()]);
+
+        assert!(matches!(registry.call("numbers", &[1]), Err(RpcError::Internal(_))));
+    }
+
+    #[test]
+    fn test_interceptors_wrap_streams_and_nested_calls() {
+        let mut registry = Registry::new();
+        registry.register_stream("numbers", |_, input, sink| {
+            for i in 0..input[0] {
+                sink.send(&[i])?;
+            }
+            Ok(())
+        });
+        registry.register_with_ctx("outer", |ctx, input| ctx.call("numbers_len", input));
+        registry.register("numbers_len", |input| Ok(vec![input[0]]));
+        registry.intercept("numbers*", |_ctx, input, next| {
+            if input[0] > 3 {
+                return Err(RpcError::Internal("too many".into()));
+            }
+            next.run(&[input[0] + 1])
+        });
+
+        let mut frames = Vec::new();
+        registry.call_stream("numbers", &[1], |f| { frames.push(f.to_vec()); true }).unwrap();
+        assert_eq!(frames, [[0], [1]]);
+        assert!(matches!(registry.call_stream("numbers", &[4], |_| true), Err(RpcError::Internal(_))));
+
+        assert_eq!(registry.call("outer", &[2]).unwrap(), [3]);
+        assert!(matches!(registry.call("outer", &[5]), Err(RpcError::Internal(_))));
+    }
+
+    #[test]
+    fn test_stream_stopped_by_consumer() {
+        let mut registry = Registry::new();
+
+        registry.register_stream("endless", |_, _, sink| loop {
+            sink.send(b"tick")?;
+        });
+
+        let mut seen = 0;
+        registry.call_stream("endless", b"", |_| { seen += 1; seen < 3 }).unwrap();
+        assert_eq!(seen, 3);
+    }
+
+    #[test]
+    fn test_cancelled_call() {
+        use crate::cancel::CancelToken;
+
+        let mut registry = Registry::new();
+        registry.register_with_ctx("spin", |ctx, _| {
+            let token = ctx.cancel_token().expect("token").clone();
+            let mut polls = 0;
+            loop {
+                ctx.check_cancelled()?;
+                polls += 1;
+                if polls == 3 {
+                    token.cancel();
+                }
+            }
+        });
+
+        let options = CallOptions::new().with_cancel(CancelToken::new());
+        assert!(matches!(registry.call_with("spin", b"", &options), Err(RpcError::Cancelled)));
+
+        // Already-cancelled tokens never reach the handler
+        registry.register("never", |_| unreachable!());
+        assert!(matches!(registry.call_with("never", b"", &options), Err(RpcError::Cancelled)));
+    }
+
+    #[test]
+    fn test_method_timeout_visible_to_handler() {
+        let mut registry = Registry::new();
+        registry
+            .register_with_ctx("cooperative", |ctx, _| {
+                let deadline = ctx.deadline().expect("method timeout sets a deadline");
+                while Instant::now() < deadline {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                ctx.check_cancelled()?;
+                Ok(vec![])
+            })
+            .timeout(Duration::from_millis(20));
+
+        assert!(matches!(registry.call("cooperative", b""), Err(RpcError::Timeout)));
+    }
+
+    #[test]
+    fn test_expired_deadline_skips_handler() {
+        let mut registry = Registry::new();
+        registry.register("never", |_| unreachable!());
+
+        let options = CallOptions::new().with_deadline(Instant::now());
+        assert!(matches!(registry.call_with("never", b"", &options), Err(RpcError::Timeout)));
+    }
+
+    #[test]
+    fn test_call_batch_stop_on_error() {
+        let mut registry = Registry::new();
+        registry.register("echo", |input| Ok(input.to_vec()));
+
+        let calls: [(&str, &[u8]); 3] = [("echo", b"a"), ("missing", b""), ("echo", b"c")];
+
+        let results = registry.call_batch(&calls, BatchMode::new());
+        assert_eq!(results[0].as_deref().unwrap(), b"a");
+        assert!(matches!(results[1], Err(RpcError::UnknownMethod(_))));
+        assert_eq!(results[2].as_deref().unwrap(), b"c");
+
+        let results = registry.call_batch(&calls, BatchMode::new().stop_on_error());
+        assert!(results[0].is_ok());
+        assert!(matches!(results[2], Err(RpcError::Cancelled)));
+    }
+
+    #[test]
+    fn test_describe_reports_metadata() {
+        let mut registry = Registry::new();
+        registry
+            .register("get", |input| Ok(input.to_vec()))
+            .description("Fetch a node")
+            .input_type("NodeId")
+            .output_type("Node")
+            .deprecated();
+        registry.register_stream("list", |_, _, _| Ok(()));
+
+        let info = registry.describe("get").unwrap();
+        assert_eq!(info.description.as_deref(), Some("Fetch a node"));
+        assert_eq!(info.input_type.as_deref(), Some("NodeId"));
+        assert_eq!(info.output_type.as_deref(), Some("Node"));
+        assert!(info.deprecated && !info.streaming);
+
+        let names: Vec<_> = registry.describe_all().into_iter().map(|i| (i.name, i.streaming)).collect();
+        assert_eq!(names, [("get".to_string(), false), ("list".to_string(), true)]);
+        assert!(registry.describe("missing").is_none());
+    }
+
+    #[test]
+    fn test_unregister_and_replace_keep_settings() {
+        let mut registry = Registry::new();
+        registry
+            .register("m", |_| Ok(b"old".to_vec()))
+            .timeout(Duration::from_secs(1))
+            .description("kept");
+
+        let previous = registry.replace("m", MethodHandler::Unary(Arc::new(|_, _| Ok(b"new".to_vec()))));
+        assert!(matches!(previous, Some(MethodHandler::Unary(_))));
+        assert_eq!(registry.call("m", b"").unwrap(), b"new");
+        let info = registry.describe("m").unwrap();
+        assert_eq!(info.description.as_deref(), Some("kept"));
+        assert_eq!(info.timeout, Some(Duration::from_secs(1)));
+
+        let Some(MethodHandler::Unary(old)) = registry.unregister("m") else {
+            panic!("expected the unary handler back");
+        };
+        assert!(!registry.has_method("m"));
+        assert!(registry.unregister("m").is_none());
+
+        // The returned handler is still callable
+        assert_eq!(old(&CallContext::new(&registry, "m", 0, &CallOptions::default()), b"").unwrap(), b"new");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_register_typed_maps_codec_errors() {
+        use crate::codec::Json;
+        use std::collections::BTreeMap;
+
+        let mut registry = Registry::new();
+        registry.register_typed::<Vec<u8>, u32, Json>("len", |items| Ok(items.len() as u32));
+        // JSON object keys must be strings
+        registry.register_typed::<(), BTreeMap<(u8, u8), u8>, Json>("grid", |()| Ok(BTreeMap::from([((0, 0), 1)])));
+
+        assert_eq!(registry.call("len", b"[1,2,3]").unwrap(), b"3");
+        assert!(matches!(registry.call("len", b"{"), Err(RpcError::ParseError(_))));
+        assert!(matches!(registry.call("grid", b"null"), Err(RpcError::SerializationError(_))));
+        assert_eq!(registry.describe("len").unwrap().input_type.as_deref(), Some("alloc::vec::Vec<u8>"));
+    }
 }
@@ -0,0 +1,658 @@
+//! Independent runtime instances
+//!
+//! A `Runtime` owns its own registry, so separate components in one process
+//! can keep separate method sets and lifecycles. The `rrpc_*` functions
+//! without an `_rt` suffix operate on a process-wide default runtime created
+//! by `rrpc_init`.
+//!
+//! Calls never hold a lock while a handler runs: the runtime publishes an
+//! immutable `Arc<Registry>` snapshot, callers clone it under a short read
+//! lock, and registration swaps in a new snapshot.
+//!
+//! Calls with a deadline (from the caller or the method's timeout) run on a
+//! separate thread so the caller gets `RpcError::Timeout` on time even if
+//! the handler never returns; the handler is left to finish on its own.
+//! Those threads are reused and capped at `MAX_TIMED_CALL_THREADS` per
+//! runtime, so stuck handlers cannot pile up threads without bound.
+//!
+//! `shutdown` rejects new calls, waits for in-flight ones (including queued
+//! async calls) and drops the handlers. Registry edits and plugin loads are
+//! rejected from then on.
+//!
+//! Lock order: `plugins`, then `writer`, then `snapshot`.
+//!
+//! Plugins loaded with `load_plugin` add their methods under a namespace;
+//! `unload_plugin` removes them again.
+
+use crate::batch::{self, BatchMode};
+use crate::context::CallOptions;
+use crate::error::RpcError;
+use crate::plugin;
+use crate::pool::{BoundedPool, WorkerPool};
+use crate::registry::{MethodHandler, Registry};
+use crate::trace;
+use parking_lot::{Condvar, Mutex, MutexGuard, RwLock};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+/// A self-contained rRPC runtime
+pub struct Runtime {
+    snapshot: RwLock<Arc<Registry>>,
+    writer: Mutex<()>,
+    pool: OnceLock<WorkerPool>,
+    timed: Arc<BoundedPool>,
+    next_call_id: AtomicU64,
+    calls: Arc<CallTracker>,
+    plugins: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl Runtime {
+    /// Create a runtime with an empty registry
+    pub fn new() -> Self {
+        Self {
+            snapshot: RwLock::new(Arc::new(Registry::new())),
+            writer: Mutex::new(()),
+            pool: OnceLock::new(),
+            timed: Arc::new(BoundedPool::new("rrpc-timed-call", MAX_TIMED_CALL_THREADS)),
+            next_call_id: AtomicU64::new(1),
+            calls: Arc::new(CallTracker::default()),
+            plugins: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the current registry snapshot
+    ///
+    /// The snapshot is immutable; later registrations are not visible in it.
+    pub fn registry(&self) -> Arc<Registry> {
+        self.snapshot.read().clone()
+    }
+
+    /// Edit the registry
+    ///
+    /// Edits are made on a private copy and published atomically when the
+    /// returned guard is dropped. Concurrent editors are serialised; calls
+    /// keep running against the previous snapshot meanwhile. Edits made
+    /// after `shutdown` are discarded; use `try_registry_mut` to detect that.
+    pub fn registry_mut(&self) -> RegistryGuard<'_> {
+        let lock = self.writer.lock();
+        let draft = Registry::clone(&self.registry());
+        RegistryGuard {
+            runtime: self,
+            _lock: lock,
+            draft,
+        }
+    }
+
+    /// Edit the registry, or fail if the runtime is shut down
+    pub fn try_registry_mut(&self) -> Result<RegistryGuard<'_>, RpcError> {
+        let registry = self.registry_mut();
+        if self.is_shut_down() {
+            return Err(RpcError::Internal("runtime is shut down".into()));
+        }
+        Ok(registry)
+    }
+
+    /// Call a method on the current snapshot
+    pub fn call(&self, method: &str, input: &[u8]) -> Result<Vec<u8>, RpcError> {
+        self.call_with(method, input, &CallOptions::default())
+    }
+
+    /// Call a method on the current snapshot with per-call options
+    ///
+    /// Returns `RpcError::Timeout` once the deadline passes, whether or not
+    /// the handler has returned.
+    pub fn call_with(&self, method: &str, input: &[u8], options: &CallOptions) -> Result<Vec<u8>, RpcError> {
+        let _call = self.calls.begin()?;
+        let registry = self.registry();
+        run(&registry, &self.timed, method, input, options)
+    }
+
+    /// Call several methods on one snapshot, returning one result per entry
+    ///
+    /// Deadlines are enforced per entry as for `call`. Entries skipped
+    /// because of `BatchMode::stop_on_error` fail with `RpcError::Cancelled`.
+    pub fn call_batch(&self, calls: &[(&str, &[u8])], mode: BatchMode) -> Vec<Result<Vec<u8>, RpcError>> {
+        self.call_batch_with(calls, mode, &CallOptions::default())
+    }
+
+    /// Call several methods on one snapshot with options shared by every entry
+    ///
+    /// Each entry gets its own copy of `options`, so with
+    /// `CallOptions::with_capabilities` every entry is checked against the token.
+    pub fn call_batch_with(
+        &self,
+        calls: &[(&str, &[u8])],
+        mode: BatchMode,
+        options: &CallOptions,
+    ) -> Vec<Result<Vec<u8>, RpcError>> {
+        match self.run_batch(calls.len(), mode, |registry, i| self.run_on(registry, calls[i].0, calls[i].1, options)) {
+            Ok(outcomes) => outcomes
+                .into_iter()
+                .map(|result| result.unwrap_or(Err(RpcError::Cancelled)))
+                .collect(),
+            Err(e) => calls.iter().map(|_| Err(e.clone())).collect(),
+        }
+    }
+
+    /// Run `len` batch entries against one snapshot as a single tracked call
+    ///
+    /// Fails without running anything once the runtime is shut down.
+    pub(crate) fn run_batch<T, E, F>(&self, len: usize, mode: BatchMode, call: F) -> Result<Vec<Option<Result<T, E>>>, RpcError>
+    where
+        T: Send,
+        E: Send,
+        F: Fn(&Arc<Registry>, usize) -> Result<T, E> + Sync,
+    {
+        let _call = self.calls.begin()?;
+        let registry = self.registry();
+        Ok(batch::run(len, mode, |i| call(&registry, i)))
+    }
+
+    /// Run one call on `registry`, enforcing its deadline on this runtime's timed-call threads
+    pub(crate) fn run_on(&self, registry: &Arc<Registry>, method: &str, input: &[u8], options: &CallOptions) -> Result<Vec<u8>, RpcError> {
+        run(registry, &self.timed, method, input, options)
+    }
+
+    /// Stream a method's output frames from the current snapshot
+    pub fn call_stream<F>(&self, method: &str, input: &[u8], on_item: F) -> Result<(), RpcError>
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        let _call = self.calls.begin()?;
+        let registry = self.registry();
+        registry.call_stream(method, input, on_item)
+    }
+
+    /// Call a method on this runtime's worker pool
+    ///
+    /// Returns immediately with a call id (never 0). `done` runs on a worker
+    /// thread with the handler's result. The pool is started on first use
+    /// and the call sees the registry snapshot current at submission time.
+    /// After `shutdown`, `done` runs immediately on the calling thread with
+    /// an error.
+    pub fn call_async<F>(&self, method: impl Into<String>, input: Vec<u8>, done: F) -> u64
+    where
+        F: FnOnce(u64, Result<Vec<u8>, RpcError>) + Send + 'static,
+    {
+        self.call_async_with(method, input, CallOptions::default(), done)
+    }
+
+    /// Call a method on the worker pool with per-call options
+    pub fn call_async_with<F>(&self, method: impl Into<String>, input: Vec<u8>, options: CallOptions, done: F) -> u64
+    where
+        F: FnOnce(u64, Result<Vec<u8>, RpcError>) + Send + 'static,
+    {
+        let id = self.next_call_id.fetch_add(1, Ordering::Relaxed);
+        let call = match self.calls.begin() {
+            Ok(call) => call,
+            Err(e) => {
+                done(id, Err(e));
+                return id;
+            }
+        };
+        let registry = self.registry();
+        let timed = Arc::clone(&self.timed);
+        let method = method.into();
+
+        self.pool.get_or_init(WorkerPool::new).execute(trace::in_current_span(move || {
+            let _call = call;
+            let result = run(&registry, &timed, &method, &input, &options);
+            drop(registry);
+            done(id, result);
+        }));
+
+        id
+    }
+
+    /// Load a plugin library and register its methods as `namespace.method`
+    ///
+    /// Returns the registered names. Fails without registering anything if
+    /// the library cannot be opened, lacks the `export_plugin!` entry
+    /// points, reports another `PLUGIN_FINGERPRINT`, or would shadow an
+    /// existing method. An empty namespace registers the names unchanged.
+    /// Nested calls made by plugin handlers must use full names.
+    ///
+    /// # Safety
+    /// Loading a library runs its initialisers and plugin handlers run
+    /// unchecked code; `path` must name a trusted plugin built with the same
+    /// compiler and `rrpc-core` version as this library.
+    pub unsafe fn load_plugin(&self, path: impl AsRef<OsStr>, namespace: &str) -> Result<Vec<String>, RpcError> {
+        let (library, methods) = plugin::load(path.as_ref())?;
+        self.install_plugin(namespace, methods, |handler| plugin::pin(handler, &library))
+    }
+
+    /// Register the methods of `methods` under `namespace` as a plugin
+    pub(crate) fn install_plugin(
+        &self,
+        namespace: &str,
+        methods: Registry,
+        wrap: impl Fn(MethodHandler) -> MethodHandler,
+    ) -> Result<Vec<String>, RpcError> {
+        let mut plugins = self.plugins.lock();
+        if plugins.contains_key(namespace) {
+            return Err(RpcError::Internal(format!("plugin namespace '{}' is already loaded", namespace)));
+        }
+
+        let names = self.try_registry_mut()?.absorb(methods, namespace, wrap)?;
+        log::info!("loaded plugin '{}' with {} methods", namespace, names.len());
+        plugins.insert(namespace.to_string(), names.clone());
+        Ok(names)
+    }
+
+    /// Remove the methods of the plugin loaded under `namespace`
+    ///
+    /// Returns the removed names. Calls already running finish first; the
+    /// library is closed once no snapshot refers to its handlers.
+    pub fn unload_plugin(&self, namespace: &str) -> Result<Vec<String>, RpcError> {
+        let names = self
+            .plugins
+            .lock()
+            .remove(namespace)
+            .ok_or_else(|| RpcError::NotFound(format!("plugin namespace '{}'", namespace)))?;
+
+        let mut registry = self.registry_mut();
+        for name in &names {
+            registry.unregister(name);
+        }
+        log::info!("unloaded plugin '{}'", namespace);
+        Ok(names)
+    }
+
+    /// Shut the runtime down
+    ///
+    /// New calls fail from now on. Blocks until in-flight and queued calls
+    /// have finished, then drops the handlers, running their destructors.
+    /// Handlers abandoned by a timed-out call are dropped once they return.
+    /// Must not be called from a handler or completion callback of this
+    /// runtime, which would wait for itself.
+    pub fn shutdown(&self) {
+        self.calls.close_and_drain();
+
+        let mut plugins = self.plugins.lock();
+        let _lock = self.writer.lock();
+        let retired = std::mem::replace(&mut *self.snapshot.write(), Arc::new(Registry::new()));
+        drop(retired);
+        plugins.clear();
+        log::debug!("runtime shut down");
+    }
+
+    /// Whether `shutdown` has been called
+    pub fn is_shut_down(&self) -> bool {
+        self.calls.state.lock().closed
+    }
+}
+
+/// Counts in-flight calls so `shutdown` can wait for them
+#[derive(Default)]
+struct CallTracker {
+    state: Mutex<TrackerState>,
+    idle: Condvar,
+}
+
+#[derive(Default)]
+struct TrackerState {
+    active: usize,
+    closed: bool,
+}
+
+impl CallTracker {
+    /// Register a call, or fail if the runtime is shut down
+    fn begin(self: &Arc<Self>) -> Result<InFlight, RpcError> {
+        let mut state = self.state.lock();
+        if state.closed {
+            return Err(RpcError::Internal("runtime is shut down".into()));
+        }
+        state.active += 1;
+        Ok(InFlight(Arc::clone(self)))
+    }
+
+    /// Reject new calls and wait for the active ones to finish
+    fn close_and_drain(&self) {
+        let mut state = self.state.lock();
+        state.closed = true;
+        while state.active > 0 {
+            self.idle.wait(&mut state);
+        }
+    }
+}
+
+/// An in-flight call; deregisters itself on drop
+struct InFlight(Arc<CallTracker>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock();
+        state.active -= 1;
+        if state.active == 0 {
+            self.0.idle.notify_all();
+        }
+    }
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Most threads a runtime keeps for calls with a deadline
+///
+/// Handlers stuck past their deadline keep their thread; once all of them
+/// are stuck, further timed calls wait for one and time out.
+pub const MAX_TIMED_CALL_THREADS: usize = 64;
+
+/// Run a call, enforcing its deadline if it has one
+fn run(
+    registry: &Arc<Registry>,
+    timed: &BoundedPool,
+    method: &str,
+    input: &[u8],
+    options: &CallOptions,
+) -> Result<Vec<u8>, RpcError> {
+    let Some(deadline) = registry.deadline_for(method, options) else {
+        return registry.call_with(method, input, options);
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let options = options.clone().with_deadline(deadline);
+    let registry = Arc::clone(registry);
+    let method = method.to_string();
+    let input = input.to_vec();
+
+    timed.execute(trace::in_current_span(move || {
+        // Calls queued behind stuck handlers may start after their deadline
+        let result = if Instant::now() < deadline {
+            registry.call_with(&method, &input, &options)
+        } else {
+            Err(RpcError::Timeout)
+        };
+        // The receiver is gone if the caller already timed out
+        let _ = tx.send(result);
+    }));
+
+    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(RpcError::Timeout),
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(RpcError::Internal("call thread exited without a result".into()))
+        }
+    }
+}
+
+/// Write access to a runtime's registry; publishes the edits on drop
+pub struct RegistryGuard<'a> {
+    runtime: &'a Runtime,
+    _lock: MutexGuard<'a, ()>,
+    draft: Registry,
+}
+
+impl Deref for RegistryGuard<'_> {
+    type Target = Registry;
+
+    fn deref(&self) -> &Registry {
+        &self.draft
+    }
+}
+
+impl DerefMut for RegistryGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Registry {
+        &mut self.draft
+    }
+}
+
+impl Drop for RegistryGuard<'_> {
+    fn drop(&mut self) {
+        let draft = std::mem::take(&mut self.draft);
+        if self.runtime.is_shut_down() {
+            log::warn!("discarding registry edits made after shutdown");
+            return;
+        }
+        *self.runtime.snapshot.write() = Arc::new(draft);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_runtimes_are_isolated() {
+        let a = Runtime::new();
+        let b = Runtime::new();
+
+        a.registry_mut().register("only_a", |_| Ok(b"a".to_vec()));
+
+        assert!(a.registry().has_method("only_a"));
+        assert!(!b.registry().has_method("only_a"));
+    }
+
+    #[test]
+    fn test_snapshot_is_immutable() {
+        let rt = Runtime::new();
+        let before = rt.registry();
+
+        rt.registry_mut().register("late", |_| Ok(vec![]));
+
+        assert!(!before.has_method("late"));
+        assert!(rt.registry().has_method("late"));
+    }
+
+    #[test]
+    fn test_calls_run_in_parallel() {
+        const THREADS: usize = 4;
+        const WORK: Duration = Duration::from_millis(100);
+
+        let rt = Arc::new(Runtime::new());
+        {
+            let mut reg = rt.registry_mut();
+            for i in 0..THREADS {
+                reg.register(format!("slow_{}", i), |_| {
+                    thread::sleep(WORK);
+                    Ok(vec![])
+                });
+            }
+        }
+
+        let start = Instant::now();
+        let workers: Vec<_> = (0..THREADS)
+            .map(|i| {
+                let rt = Arc::clone(&rt);
+                thread::spawn(move || rt.call(&format!("slow_{}", i), b"").unwrap())
+            })
+            .collect();
+        for w in workers {
+            w.join().unwrap();
+        }
+
+        // Serialised dispatch would take THREADS * WORK
+        assert!(start.elapsed() < WORK * (THREADS as u32 - 1));
+    }
+
+    #[test]
+    fn test_call_async() {
+        let rt = Runtime::new();
+        rt.registry_mut().register_async("later", |input: Vec<u8>| async move {
+            Ok([b"later:".as_slice(), &input].concat())
+        });
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let id = rt.call_async("later", b"x".to_vec(), move |id, result| {
+            tx.send((id, result)).unwrap();
+        });
+
+        let (done_id, result) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(done_id, id);
+        assert_eq!(result.unwrap(), b"later:x");
+    }
+
+    #[test]
+    fn test_stuck_handler_times_out() {
+        let rt = Runtime::new();
+        rt.registry_mut()
+            .register("stuck", |_| {
+                thread::sleep(Duration::from_secs(2));
+                Ok(vec![])
+            })
+            .timeout(Duration::from_millis(50));
+        rt.registry_mut().register("quick", |_| Ok(b"ok".to_vec()));
+
+        let start = Instant::now();
+        assert!(matches!(rt.call("stuck", b""), Err(RpcError::Timeout)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // Per-call deadlines apply to methods without a default timeout
+        let options = CallOptions::new().with_timeout(Duration::from_secs(5));
+        assert_eq!(rt.call_with("quick", b"", &options).unwrap(), b"ok");
+    }
+
+    #[test]
+    fn test_stuck_handlers_use_bounded_threads() {
+        let rt = Runtime::new();
+        let gate = Arc::new(RwLock::new(()));
+        let held = gate.write();
+        {
+            let gate = Arc::clone(&gate);
+            rt.registry_mut()
+                .register("stuck", move |_| {
+                    drop(gate.read());
+                    Ok(vec![])
+                })
+                .timeout(Duration::from_millis(5));
+        }
+
+        for _ in 0..MAX_TIMED_CALL_THREADS + 16 {
+            assert!(matches!(rt.call("stuck", b""), Err(RpcError::Timeout)));
+        }
+        assert_eq!(rt.timed.threads(), MAX_TIMED_CALL_THREADS);
+
+        // Once the handlers return, their threads serve new calls
+        drop(held);
+        let options = CallOptions::new().with_timeout(Duration::from_secs(5));
+        assert_eq!(rt.call_with("stuck", b"", &options).unwrap(), b"");
+        assert_eq!(rt.timed.threads(), MAX_TIMED_CALL_THREADS);
+    }
+
+    #[test]
+    fn test_register_while_call_in_flight() {
+        let rt = Arc::new(Runtime::new());
+        rt.registry_mut().register("slow", |_| {
+            thread::sleep(Duration::from_millis(100));
+            Ok(vec![])
+        });
+
+        let caller = {
+            let rt = Arc::clone(&rt);
+            thread::spawn(move || rt.call("slow", b""))
+        };
+        thread::sleep(Duration::from_millis(20));
+
+        // Does not wait for the in-flight call
+        let start = Instant::now();
+        rt.registry_mut().register("fast", |_| Ok(vec![]));
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        caller.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_drains_and_drops_handlers() {
+        let rt = Arc::new(Runtime::new());
+        let resource = Arc::new(());
+        {
+            let resource = Arc::clone(&resource);
+            rt.registry_mut().register("slow", move |_| {
+                let _held = &resource;
+                thread::sleep(Duration::from_millis(100));
+                Ok(b"done".to_vec())
+            });
+        }
+        assert_eq!(Arc::strong_count(&resource), 2);
+
+        let caller = {
+            let rt = Arc::clone(&rt);
+            thread::spawn(move || rt.call("slow", b""))
+        };
+        thread::sleep(Duration::from_millis(20));
+
+        rt.shutdown();
+
+        // The in-flight call completed before the handlers were dropped
+        assert_eq!(caller.join().unwrap().unwrap(), b"done");
+        assert_eq!(Arc::strong_count(&resource), 1);
+        assert!(rt.is_shut_down());
+        assert!(matches!(rt.call("slow", b""), Err(RpcError::Internal(_))));
+    }
+
+    #[test]
+    fn test_plugin_methods_are_namespaced_and_unloadable() {
+        let rt = Runtime::new();
+        rt.registry_mut().register("geo.area", |_| Ok(vec![]));
+
+        let mut methods = Registry::new();
+        methods.register("area", |input| Ok(vec![input.iter().product()]));
+        methods.register_stream("points", |_, _, sink| sink.send(b"p"));
+
+        // Would shadow an existing method
+        let clash = rt.install_plugin("geo", methods.clone(), |h| h);
+        assert!(matches!(clash, Err(RpcError::Internal(_))));
+        assert!(!rt.registry().has_method("geo.points"));
+
+        let names = rt.install_plugin("shapes", methods.clone(), |h| h).unwrap();
+        assert_eq!(names, ["shapes.area", "shapes.points"]);
+        assert_eq!(rt.call("shapes.area", &[2, 3]).unwrap(), [6]);
+        assert!(rt.install_plugin("shapes", methods, |h| h).is_err());
+
+        assert_eq!(rt.unload_plugin("shapes").unwrap(), names);
+        assert!(!rt.registry().has_method("shapes.area"));
+        assert!(matches!(rt.unload_plugin("shapes"), Err(RpcError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_edits_after_shutdown_are_rejected() {
+        let rt = Runtime::new();
+        rt.shutdown();
+
+        rt.registry_mut().register("late", |_| Ok(vec![]));
+        assert!(!rt.registry().has_method("late"));
+        assert!(matches!(rt.try_registry_mut(), Err(RpcError::Internal(_))));
+        let loaded = rt.install_plugin("late", Registry::new(), |h| h);
+        assert!(matches!(loaded, Err(RpcError::Internal(ref m)) if m.contains("shut down")));
+    }
+
+    #[test]
+    fn test_shutdown_races_plugin_load() {
+        for _ in 0..200 {
+            let rt = Arc::new(Runtime::new());
+            let mut methods = Registry::new();
+            methods.register("area", |_| Ok(vec![]));
+
+            let loader = {
+                let rt = Arc::clone(&rt);
+                thread::spawn(move || rt.install_plugin("shapes", methods, |h| h))
+            };
+            rt.shutdown();
+            let loaded = loader.join().unwrap();
+
+            // Whichever won, nothing survives the shutdown
+            if let Err(e) = loaded {
+                assert!(matches!(e, RpcError::Internal(ref m) if m.contains("shut down")));
+            }
+            assert!(!rt.registry().has_method("shapes.area"));
+            assert!(matches!(rt.unload_plugin("shapes"), Err(RpcError::NotFound(_))));
+        }
+    }
+
+    #[test]
+    fn test_load_plugin_reports_missing_library() {
+        let rt = Runtime::new();
+        let result = unsafe { rt.load_plugin("/nonexistent/librrpc_plugin.so", "x") };
+        assert!(matches!(result, Err(RpcError::Internal(ref m)) if m.contains("/nonexistent/")));
+    }
+}
@@ -0,0 +1,267 @@
+//! Per-method call metrics
+//!
+//! Every call that reaches a registered method is counted: calls, errors by
+//! `RpcError::kind`, bytes in and out, and a latency histogram. Calls
+//! rejected before the handler runs (missing capabilities, already cancelled
+//! or past their deadline) count as errors with zero latency. Calls of
+//! unknown methods are not recorded. Latency is measured from just before
+//! the first interceptor to the end of the call, so for streaming methods
+//! it includes the time spent in the consumer.
+//!
+//! Counters are shared by every clone of a registry, so they survive
+//! `Runtime` publishing new snapshots. Read them with `Registry::stats`, or
+//! from the host with `rrpc_stats`, which returns `to_json` of them.
+
+use crate::error::RpcError;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Upper bounds, in microseconds, of the latency histogram buckets
+///
+/// A last bucket counts the calls slower than the largest bound.
+pub const LATENCY_BOUNDS_US: [u64; 16] = [
+    10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000,
+];
+
+/// Error kinds counted separately, in `RpcError` declaration order
+const ERROR_KINDS: [&str; 9] = [
+    "unknown_method",
+    "not_found",
+    "parse_error",
+    "serialization",
+    "internal",
+    "panic",
+    "cancelled",
+    "timeout",
+    "permission_denied",
+];
+
+/// Snapshot of one method's counters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodStats {
+    /// Method name
+    pub name: String,
+    /// Calls that reached the method, successful or not
+    pub calls: u64,
+    /// Failed calls by `RpcError::kind`, omitting kinds that never occurred
+    pub errors: Vec<(&'static str, u64)>,
+    /// Input bytes received
+    pub bytes_in: u64,
+    /// Output bytes returned by successful calls (frame bytes for streams)
+    pub bytes_out: u64,
+    /// Total time spent in the method
+    pub latency_total: Duration,
+    /// Slowest call
+    pub latency_max: Duration,
+    /// Calls per latency bucket; see `LATENCY_BOUNDS_US`
+    pub latency_buckets: [u64; LATENCY_BOUNDS_US.len() + 1],
+}
+
+impl MethodStats {
+    /// Number of failed calls
+    pub fn error_count(&self) -> u64 {
+        self.errors.iter().map(|(_, n)| n).sum()
+    }
+
+    /// Mean call latency, if the method was called
+    pub fn latency_mean(&self) -> Option<Duration> {
+        let calls = u32::try_from(self.calls).ok().filter(|&n| n > 0)?;
+        Some(self.latency_total / calls)
+    }
+}
+
+/// Live counters of one method
+#[derive(Default)]
+struct Counters {
+    calls: AtomicU64,
+    errors: [AtomicU64; ERROR_KINDS.len()],
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    latency_total_us: AtomicU64,
+    latency_max_us: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BOUNDS_US.len() + 1],
+}
+
+impl Counters {
+    fn snapshot(&self, name: &str) -> MethodStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        MethodStats {
+            name: name.to_string(),
+            calls: load(&self.calls),
+            errors: ERROR_KINDS
+                .iter()
+                .zip(&self.errors)
+                .map(|(kind, count)| (*kind, load(count)))
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+            bytes_in: load(&self.bytes_in),
+            bytes_out: load(&self.bytes_out),
+            latency_total: Duration::from_micros(load(&self.latency_total_us)),
+            latency_max: Duration::from_micros(load(&self.latency_max_us)),
+            latency_buckets: std::array::from_fn(|i| load(&self.latency_buckets[i])),
+        }
+    }
+}
+
+/// Counters of every method of a registry
+#[derive(Default)]
+pub(crate) struct Collector {
+    methods: RwLock<HashMap<String, Arc<Counters>>>,
+}
+
+impl Collector {
+    /// Record one finished call; `outcome` carries the output size on success
+    pub(crate) fn record(&self, method: &str, bytes_in: usize, outcome: Result<usize, &RpcError>, elapsed: Duration) {
+        let counters = self.counters(method);
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+
+        counters.calls.fetch_add(1, Ordering::Relaxed);
+        counters.bytes_in.fetch_add(bytes_in as u64, Ordering::Relaxed);
+        match outcome {
+            Ok(bytes_out) => {
+                counters.bytes_out.fetch_add(bytes_out as u64, Ordering::Relaxed);
+            }
+            Err(e) => {
+                let kind = ERROR_KINDS.iter().position(|k| *k == e.kind()).unwrap_or(0);
+                counters.errors[kind].fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        counters.latency_total_us.fetch_add(micros, Ordering::Relaxed);
+        counters.latency_max_us.fetch_max(micros, Ordering::Relaxed);
+        let bucket = LATENCY_BOUNDS_US.partition_point(|&bound| bound < micros);
+        counters.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Snapshot of every recorded method, sorted by name
+    pub(crate) fn snapshot(&self) -> Vec<MethodStats> {
+        let mut stats: Vec<_> = self
+            .methods
+            .read()
+            .iter()
+            .map(|(name, counters)| counters.snapshot(name))
+            .collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
+
+    /// Forget every counter
+    pub(crate) fn reset(&self) {
+        self.methods.write().clear();
+    }
+
+    fn counters(&self, method: &str) -> Arc<Counters> {
+        if let Some(counters) = self.methods.read().get(method) {
+            return Arc::clone(counters);
+        }
+        Arc::clone(self.methods.write().entry(method.to_string()).or_default())
+    }
+}
+
+/// Encode method statistics as a JSON object
+///
+/// The object has `latency_bounds_us` (the bucket bounds) and `methods`, an
+/// array of `{"name", "calls", "errors", "bytes_in", "bytes_out",
+/// "latency_us"}`. `errors` maps error kinds to counts; `latency_us` has
+/// `total`, `max` and `buckets`, which has one more entry than the bounds.
+pub fn to_json(stats: &[MethodStats]) -> String {
+    let mut out = String::from("{\"latency_bounds_us\":");
+    push_list(&mut out, LATENCY_BOUNDS_US.iter());
+    out.push_str(",\"methods\":[");
+    for (i, method) in stats.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"name\":");
+        crate::introspect::push_str(&mut out, Some(&method.name));
+        let _ = write!(out, ",\"calls\":{},\"errors\":{{", method.calls);
+        for (j, (kind, count)) in method.errors.iter().enumerate() {
+            let _ = write!(out, "{}\"{}\":{}", if j > 0 { "," } else { "" }, kind, count);
+        }
+        let _ = write!(
+            out,
+            "}},\"bytes_in\":{},\"bytes_out\":{},\"latency_us\":{{\"total\":{},\"max\":{},\"buckets\":",
+            method.bytes_in,
+            method.bytes_out,
+            method.latency_total.as_micros(),
+            method.latency_max.as_micros()
+        );
+        push_list(&mut out, method.latency_buckets.iter());
+        out.push_str("}}");
+    }
+    out.push_str("]}");
+    out
+}
+
+/// Append a JSON array of numbers
+fn push_list<'a>(out: &mut String, values: impl Iterator<Item = &'a u64>) {
+    out.push('[');
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(out, "{}", value);
+    }
+    out.push(']');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Registry;
+
+    #[test]
+    fn test_record_buckets_and_errors() {
+        let collector = Collector::default();
+        collector.record("m", 3, Ok(5), Duration::from_micros(10));
+        collector.record("m", 4, Err(&RpcError::Timeout), Duration::from_micros(11));
+        collector.record("m", 1, Err(&RpcError::Timeout), Duration::from_secs(2));
+
+        let stats = collector.snapshot();
+        assert_eq!(stats.len(), 1);
+        let m = &stats[0];
+        assert_eq!((m.calls, m.bytes_in, m.bytes_out), (3, 8, 5));
+        assert_eq!(m.errors, [("timeout", 2)]);
+        assert_eq!(m.error_count(), 2);
+        assert_eq!((m.latency_buckets[0], m.latency_buckets[1], m.latency_buckets[16]), (1, 1, 1));
+        assert_eq!(m.latency_max, Duration::from_secs(2));
+
+        collector.reset();
+        assert!(collector.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_registry_stats_shared_by_clones() {
+        let mut registry = Registry::new();
+        registry.register("echo", |input| Ok(input.to_vec()));
+        registry.register("fail", |_| Err(RpcError::NotFound("x".into())));
+        registry.register_stream("frames", |_, _, sink| {
+            sink.send(b"ab")?;
+            sink.send(b"cde")
+        });
+
+        let snapshot = registry.clone();
+        snapshot.call("echo", b"hello").unwrap();
+        snapshot.call("fail", b"").unwrap_err();
+        snapshot.call("missing", b"").unwrap_err();
+        snapshot.call_stream("frames", b"x", |_| true).unwrap();
+
+        let stats = registry.stats();
+        let names: Vec<_> = stats.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["echo", "fail", "frames"]);
+        assert_eq!((stats[0].calls, stats[0].bytes_in, stats[0].bytes_out), (1, 5, 5));
+        assert_eq!(stats[1].errors, [("not_found", 1)]);
+        assert_eq!(stats[2].bytes_out, 5);
+
+        let json = to_json(&stats[1..2]);
+        assert!(json.starts_with(r#"{"latency_bounds_us":[10,25,"#), "{}", json);
+        assert!(
+            json.contains(r#""methods":[{"name":"fail","calls":1,"errors":{"not_found":1},"bytes_in":0,"bytes_out":0,"latency_us":{"total":"#),
+            "{}",
+            json
+        );
+    }
+}
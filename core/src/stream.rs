@@ -0,0 +1,61 @@
+//! Server-streaming methods
+//!
+//! A streaming handler pushes any number of output frames into a
+//! `StreamSink` instead of returning one buffer. Frames are forwarded to the
+//! consumer as they are produced.
+
+use crate::context::{CallContext, CallOptions};
+use crate::error::RpcError;
+use std::sync::Arc;
+
+/// Streaming handler type: (call context, input bytes, sink) → Result<(), error>
+pub type StreamHandler =
+    Arc<dyn Fn(&CallContext<'_>, &[u8], &mut StreamSink<'_>) -> Result<(), RpcError> + Send + Sync>;
+
+/// Receiver of the frames produced by a streaming handler
+pub struct StreamSink<'a> {
+    consumer: &'a mut dyn FnMut(&[u8]) -> bool,
+    options: &'a CallOptions,
+    closed: bool,
+    sent: usize,
+}
+
+impl<'a> StreamSink<'a> {
+    /// Wrap a consumer; returning `false` from it closes the stream
+    pub(crate) fn new(consumer: &'a mut dyn FnMut(&[u8]) -> bool, options: &'a CallOptions) -> Self {
+        Self {
+            consumer,
+            options,
+            closed: false,
+            sent: 0,
+        }
+    }
+
+    /// Push one frame to the consumer
+    ///
+    /// Fails with `RpcError::Cancelled` once the consumer has closed the
+    /// stream or the call was cancelled, and with `RpcError::Timeout` past
+    /// the deadline; handlers should propagate the error with `?` to stop
+    /// producing.
+    pub fn send(&mut self, frame: &[u8]) -> Result<(), RpcError> {
+        if self.closed {
+            return Err(RpcError::Cancelled);
+        }
+        self.options.check()?;
+        if !(self.consumer)(frame) {
+            self.closed = true;
+        }
+        self.sent += 1;
+        Ok(())
+    }
+
+    /// Whether the consumer has closed the stream
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Number of frames delivered so far
+    pub fn frames_sent(&self) -> usize {
+        self.sent
+    }
+}
@@ -0,0 +1,164 @@
+//! `tracing` spans around host calls
+//!
+//! With the `tracing` feature, every `rrpc_call`, `rrpc_call_with_token`,
+//! `rrpc_call_with_timeout`, `rrpc_call_with_capabilities` and
+//! `rrpc_call_into`, and every entry of `rrpc_call_batch`, runs inside an
+//! `rrpc_call` span recording `method` and `in_len`, and once the call
+//! returns `out_len`, `rc` and `duration_us`. Events emitted by handlers
+//! nest under it, including handlers moved to another thread to enforce a
+//! deadline, async calls and parallel batch entries, which run in the span
+//! current when they were submitted.
+//!
+//! Without the feature these helpers compile to nothing.
+
+use std::os::raw::c_int;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+/// Span of one host call
+pub(crate) struct CallSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    started: Instant,
+}
+
+impl CallSpan {
+    /// Open the span of a call to `method`
+    pub(crate) fn new(method: &str, in_len: usize) -> Self {
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::info_span!(
+                "rrpc_call",
+                method,
+                in_len,
+                out_len = tracing::field::Empty,
+                rc = tracing::field::Empty,
+                duration_us = tracing::field::Empty,
+            );
+            Self {
+                span,
+                started: Instant::now(),
+            }
+        }
+        #[cfg(not(feature = "tracing"))]
+        {
+            let _ = (method, in_len);
+            Self {}
+        }
+    }
+
+    /// Run `f` inside the span
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "tracing")]
+        return self.span.in_scope(f);
+        #[cfg(not(feature = "tracing"))]
+        f()
+    }
+
+    /// Record the outcome and close the span
+    pub(crate) fn finish(self, rc: c_int, out_len: usize) {
+        #[cfg(feature = "tracing")]
+        {
+            let duration_us = u64::try_from(self.started.elapsed().as_micros()).unwrap_or(u64::MAX);
+            self.span.record("out_len", out_len);
+            self.span.record("rc", rc);
+            self.span.record("duration_us", duration_us);
+            tracing::debug!(parent: &self.span, rc, out_len, duration_us, "rrpc call finished");
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = (rc, out_len);
+    }
+}
+
+/// Wrap `f` so it runs in the span current now, wherever it is called
+pub(crate) fn in_current_span<T>(f: impl FnOnce() -> T) -> impl FnOnce() -> T {
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        move || span.in_scope(f)
+    }
+    #[cfg(not(feature = "tracing"))]
+    f
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    /// Records each event's parent span and the fields recorded on spans
+    #[derive(Clone, Default)]
+    struct Recorder {
+        events: Arc<Mutex<Vec<Option<String>>>>,
+        fields: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+            self.fields.lock().push(format!("{:?}", attrs.values()));
+        }
+
+        fn on_record(&self, _: &Id, values: &Record<'_>, _: Context<'_, S>) {
+            self.fields.lock().push(format!("{:?}", values));
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let parent = ctx.event_span(event).map(|span| span.name().to_string());
+            self.events.lock().push(parent);
+        }
+    }
+
+    #[test]
+    fn test_handler_events_nest_under_call_span() {
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let span = CallSpan::new("echo", 3);
+        // Run outside the span, as a handler moved to another thread would
+        let deferred = span.in_scope(|| in_current_span(|| tracing::info!("in handler")));
+        deferred();
+        span.finish(0, 5);
+
+        let events = recorder.events.lock();
+        assert_eq!(*events, [Some("rrpc_call".to_string()), Some("rrpc_call".to_string())]);
+        let fields = recorder.fields.lock().join(" ");
+        assert!(fields.contains(r#"method: "echo""#) && fields.contains("in_len: 3"), "{}", fields);
+        assert!(fields.contains("out_len: 5") && fields.contains("rc: 0"), "{}", fields);
+    }
+
+    #[test]
+    fn test_batch_entries_get_their_own_span() {
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let rt = crate::Runtime::new();
+        rt.registry_mut().register("echo", |input| {
+            tracing::info!("in handler");
+            Ok(input.to_vec())
+        });
+        let entries = [
+            crate::BatchEntry { method: c"echo".as_ptr(), in_ptr: b"abc".as_ptr(), in_len: 3 },
+            crate::BatchEntry { method: c"missing".as_ptr(), in_ptr: std::ptr::null(), in_len: 0 },
+        ];
+        let mut results = [(); 2].map(|_| crate::BatchResult { rc: -1, out_ptr: std::ptr::null_mut(), out_len: 0 });
+        let rc = unsafe { crate::rrpc_call_batch_rt(&rt, entries.as_ptr(), entries.len(), 0, results.as_mut_ptr()) };
+        assert_eq!(rc, crate::ERR_UNKNOWN_METHOD);
+        unsafe { crate::rrpc_free(results[0].out_ptr, results[0].out_len) };
+
+        // The handler's event and each entry's "finished" event
+        assert_eq!(recorder.events.lock().len(), 3);
+        assert!(recorder.events.lock().iter().all(|parent| parent.as_deref() == Some("rrpc_call")));
+        let fields = recorder.fields.lock().join(" ");
+        assert!(fields.contains(r#"method: "echo""#) && fields.contains("out_len: 3"), "{}", fields);
+        assert!(fields.contains(r#"method: "missing""#), "{}", fields);
+        assert!(fields.contains(&format!("rc: {}", crate::ERR_UNKNOWN_METHOD)), "{}", fields);
+    }
+}
@@ -0,0 +1,33 @@
+//! Loads the example plugin (examples/plugin.rs) into a runtime
+//!
+//! `cargo test` builds the examples, with the same features as this test,
+//! before running any test, so the library is already next to this binary.
+//! When running this test on its own with `cargo test --test plugin`, build
+//! it first with `cargo build --example plugin` and the same features.
+
+use rrpc_core::{RpcError, Runtime};
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::path::PathBuf;
+
+/// Path of the example plugin built for this test's profile
+fn example_plugin() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let path = exe.parent().unwrap().with_file_name("examples").join(format!("{}plugin{}", DLL_PREFIX, DLL_SUFFIX));
+    assert!(path.exists(), "{} is missing; run `cargo build --example plugin` first", path.display());
+    path
+}
+
+#[test]
+fn test_load_example_plugin() {
+    let rt = Runtime::new();
+    let names = unsafe { rt.load_plugin(example_plugin(), "geo") }.unwrap();
+    assert_eq!(names, ["geo.area", "geo.perimeter"]);
+
+    let rect = [3u32.to_le_bytes(), 4u32.to_le_bytes()].concat();
+    assert_eq!(rt.call("geo.area", &rect).unwrap(), 12u64.to_le_bytes());
+    assert_eq!(rt.call("geo.perimeter", &rect).unwrap(), 14u64.to_le_bytes());
+    assert!(matches!(rt.call("geo.area", b"bad"), Err(RpcError::ParseError(_))));
+
+    rt.unload_plugin("geo").unwrap();
+    assert!(!rt.registry().has_method("geo.area"));
+}
@@ -1,40 +1,55 @@
 //! Simple echo example demonstrating rRPC
 
-use rrpc_core::{get_registry, rrpc_init, RpcError};
+use rrpc_core::{default_runtime, rrpc_init, rrpc_set_log_callback, RpcError};
+use std::os::raw::{c_int, c_void};
 
+#[rrpc_core::method("echo")]
 fn echo_handler(input: &[u8]) -> Result<Vec<u8>, RpcError> {
-    println!("Echo received: {} bytes", input.len());
+    log::info!("Echo received: {} bytes", input.len());
     Ok(input.to_vec())
 }
 
+#[rrpc_core::method("reverse")]
 fn reverse_handler(input: &[u8]) -> Result<Vec<u8>, RpcError> {
     let mut result = input.to_vec();
     result.reverse();
-    println!("Reversed {} bytes", result.len());
+    log::info!("Reversed {} bytes", result.len());
     Ok(result)
 }
 
+/// Stands in for the host's logging pipeline
+extern "C" fn print_log(
+    level: c_int,
+    _target_ptr: *const u8,
+    _target_len: usize,
+    msg_ptr: *const u8,
+    msg_len: usize,
+    _user_data: *mut c_void,
+) {
+    let message = unsafe { std::slice::from_raw_parts(msg_ptr, msg_len) };
+    println!("  [log {}] {}", level, String::from_utf8_lossy(message));
+}
+
 fn main() {
     println!("=== rRPC Echo Demo ===\n");
     
-    // Initialize rRPC runtime
+    // Forward handler logs to the host
+    unsafe {
+        rrpc_set_log_callback(Some(print_log), std::ptr::null_mut());
+    }
+    
+    // Initialize rRPC runtime; #[rrpc_core::method] handlers are registered automatically
     unsafe {
         rrpc_init();
     }
     println!("✓ rRPC initialized");
     
-    // Register handlers
-    let registry = get_registry().unwrap();
-    {
-        let mut reg = registry.lock();
-        reg.register("echo", echo_handler);
-        reg.register("reverse", reverse_handler);
-    }
-    println!("✓ Registered 2 handlers: echo, reverse\n");
+    let runtime = default_runtime().unwrap();
+    println!("✓ Registered handlers: {}\n", runtime.registry().methods().join(", "));
     
     // Test echo
     {
-        let reg = registry.lock();
+        let reg = runtime.registry();
         let input = b"Hello, rRPC!";
         println!("Calling 'echo' with: {:?}", std::str::from_utf8(input).unwrap());
         
@@ -50,7 +65,7 @@ fn main() {
     
     // Test reverse
     {
-        let reg = registry.lock();
+        let reg = runtime.registry();
         let input = b"Hello, rRPC!";
         println!("Calling 'reverse' with: {:?}", std::str::from_utf8(input).unwrap());
         
@@ -66,7 +81,7 @@ fn main() {
     
     // Test unknown method
     {
-        let reg = registry.lock();
+        let reg = runtime.registry();
         println!("Calling unknown method 'missing'...");
         match reg.call("missing", b"test") {
             Ok(_) => println!("  Unexpected success"),
@@ -0,0 +1,34 @@
+//! Example plugin: a cdylib adding geometry methods to a host runtime
+//!
+//! Build with `cargo build --example plugin`, then load the resulting
+//! library with `rrpc_load_plugin(path, "geo")` to get `geo.area` and
+//! `geo.perimeter`.
+
+use rrpc_core::{Registry, RpcError};
+
+/// Parse a `width x height` pair of little-endian u32s
+fn rect(input: &[u8]) -> Result<(u64, u64), RpcError> {
+    let bytes: [u8; 8] = input
+        .try_into()
+        .map_err(|_| RpcError::ParseError(format!("expected 8 bytes, got {}", input.len())))?;
+    let width = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let height = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    Ok((u64::from(width), u64::from(height)))
+}
+
+fn register(registry: &mut Registry) {
+    registry
+        .register("area", |input| {
+            let (w, h) = rect(input)?;
+            Ok((w * h).to_le_bytes().to_vec())
+        })
+        .description("Area of a width x height rectangle");
+    registry
+        .register("perimeter", |input| {
+            let (w, h) = rect(input)?;
+            Ok((2 * (w + h)).to_le_bytes().to_vec())
+        })
+        .description("Perimeter of a width x height rectangle");
+}
+
+rrpc_core::export_plugin!(register);
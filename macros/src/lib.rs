@@ -0,0 +1,203 @@
+//! # rRPC Macros
+//!
+//! Attribute macros for declaring rRPC handlers. Use them through the
+//! re-exports in `rrpc-core` (`rrpc_core::method`) rather than depending on
+//! this crate directly; the generated code refers to `::rrpc_core`.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Expr, FnArg, ItemFn, Lit, LitStr, Meta, Path, Token, Type};
+
+/// Declare a function as an rRPC method
+///
+/// The function is kept as written. A registration is submitted to the
+/// link-time method collection, which `rrpc_init` (or
+/// `Registry::register_collected`) installs.
+///
+/// The function must be a free, non-async, non-generic function with one
+/// argument returning `Result<T, RpcError>`:
+///
+/// - `fn(&[u8]) -> Result<Vec<u8>, RpcError>` is registered as-is
+/// - any other `fn(Req) -> Result<Resp, RpcError>` is registered with
+///   `Registry::register_typed`, using `codec::Json` unless another codec is
+///   named with `codec = ...`
+///
+/// The method name defaults to the function name. Doc comments become the
+/// method description. Each `requires = "capability"` makes callers need
+/// that capability (see `MethodConfig::requires`); it may be repeated.
+/// `audited` records every call in the audit log (`MethodConfig::audited`).
+///
+/// # Example
+/// ```ignore
+/// use rrpc_core::RpcError;
+///
+/// /// List the nodes of a graph
+/// #[rrpc_core::method("udg.list_nodes")]
+/// fn list_nodes(req: ListReq) -> Result<Vec<Node>, RpcError> {
+///     // ...
+/// }
+///
+/// #[rrpc_core::method("udg.delete_node", requires = "graph.write", audited)]
+/// fn delete_node(req: DeleteReq) -> Result<(), RpcError> {
+///     // ...
+/// }
+///
+/// #[rrpc_core::method("udg.get_node", codec = rrpc_core::codec::MsgPack)]
+/// fn get_node(req: GetReq) -> Result<Node, RpcError> {
+///     // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn method(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as MethodArgs);
+    let item = parse_macro_input!(item as ItemFn);
+
+    match expand(args, item) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Arguments of `#[method]`: `["name"] [, codec = Path] [, requires = "capability"]* [, audited]`
+struct MethodArgs {
+    name: Option<LitStr>,
+    codec: Option<Path>,
+    requires: Vec<LitStr>,
+    audited: bool,
+}
+
+impl Parse for MethodArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = MethodArgs {
+            name: None,
+            codec: None,
+            requires: Vec::new(),
+            audited: false,
+        };
+
+        if input.peek(LitStr) {
+            args.name = Some(input.parse()?);
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            if key == "audited" {
+                args.audited = true;
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+                continue;
+            }
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "codec" if args.codec.is_none() => args.codec = Some(input.parse()?),
+                "codec" => return Err(syn::Error::new(key.span(), "duplicate `codec`")),
+                "requires" => args.requires.push(input.parse()?),
+                _ => return Err(syn::Error::new(key.span(), "unknown argument, expected `codec`, `requires` or `audited`")),
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+fn expand(args: MethodArgs, item: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let sig = &item.sig;
+    if let Some(token) = &sig.asyncness {
+        return Err(syn::Error::new_spanned(token, "#[method] does not support async fn; use Registry::register_async"));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&sig.generics, "#[method] functions cannot be generic"));
+    }
+
+    let mut inputs = sig.inputs.iter();
+    let (Some(arg), None) = (inputs.next(), inputs.next()) else {
+        return Err(syn::Error::new_spanned(&sig.inputs, "#[method] functions take exactly one argument"));
+    };
+    let arg = match arg {
+        FnArg::Typed(arg) => arg,
+        FnArg::Receiver(arg) => {
+            return Err(syn::Error::new_spanned(arg, "#[method] must be on a free function"));
+        }
+    };
+
+    let ident = &sig.ident;
+    let name = args
+        .name
+        .unwrap_or_else(|| LitStr::new(&ident.to_string(), Span::call_site()));
+
+    let register = if is_byte_slice(&arg.ty) {
+        if let Some(codec) = &args.codec {
+            return Err(syn::Error::new_spanned(codec, "`codec` has no effect on a `&[u8]` handler"));
+        }
+        quote! { registry.register(#name, #ident) }
+    } else {
+        let codec = match &args.codec {
+            Some(codec) => quote! { #codec },
+            None => quote! { ::rrpc_core::codec::Json },
+        };
+        quote! { registry.register_typed::<_, _, #codec>(#name, #ident) }
+    };
+
+    let description = doc_string(&item).map(|doc| quote! { .description(#doc) });
+    let requires = &args.requires;
+    let audited = args.audited.then(|| quote! { .audited() });
+
+    Ok(quote! {
+        #item
+
+        const _: () = {
+            fn register(registry: &mut ::rrpc_core::Registry) {
+                #register #description #(.requires(#requires))* #audited;
+            }
+
+            ::rrpc_core::__private::inventory::submit! {
+                ::rrpc_core::registration::MethodRegistration::new(#name, register)
+            }
+        };
+    })
+}
+
+/// Whether `ty` is `&[u8]`
+fn is_byte_slice(ty: &Type) -> bool {
+    let Type::Reference(reference) = ty else {
+        return false;
+    };
+    if reference.mutability.is_some() {
+        return false;
+    }
+    let Type::Slice(slice) = &*reference.elem else {
+        return false;
+    };
+    matches!(&*slice.elem, Type::Path(path) if path.qself.is_none() && path.path.is_ident("u8"))
+}
+
+/// The function's doc comment, with line indentation removed
+fn doc_string(item: &ItemFn) -> Option<String> {
+    let lines: Vec<String> = item
+        .attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(doc) => Some(doc.value().trim().to_string()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    let doc = lines.join("\n");
+    let doc = doc.trim();
+    (!doc.is_empty()).then(|| doc.to_string())
+}